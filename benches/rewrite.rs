@@ -0,0 +1,153 @@
+//! Times a dry run and a forced run over a synthetic project.
+//!
+//! The project has `BENCH_ASSETS` assets (default 4000), each with a `.meta`
+//! and a prefab referencing `BENCH_REFS` (default 20) other assets, so the
+//! mapping grows with the project the way it does in real ones.
+//!
+//! It then searches the same files in memory with mappings of 1%, 10% and
+//! 100% of the GUIDs, next to the old loop calling `match_indices` once per
+//! GUID, to show the time no longer grows with the size of the mapping.
+//!
+//!     cargo bench --bench rewrite
+
+use std::{
+    fmt::Write as _,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, Instant},
+};
+
+use rayon::prelude::*;
+use unity_guid_rewriter::{GuidMapping, Rewriter};
+
+fn main() {
+    let assets = env_usize("BENCH_ASSETS", 4000);
+    let refs = env_usize("BENCH_REFS", 20);
+
+    let root = std::env::temp_dir().join("unity-guid-rewriter-bench");
+    let _ = std::fs::remove_dir_all(&root);
+    let guids = generate(&root, assets, refs);
+    let files = load(&root);
+
+    println!("{} assets, {} refs each", assets, refs);
+    println!("{:>8} {:>12} {:>12}", "GUIDs", "automaton", "per GUID");
+    for percent in [1, 10, 100] {
+        let mapping = GuidMapping::from_pairs(
+            guids[..guids.len() * percent / 100]
+                .iter()
+                .map(|guid| (guid.clone(), guid.chars().rev().collect())),
+        );
+        let automaton = time_in_memory(|| {
+            let report = Rewriter::new(&mapping).run_in_memory(&mut files.clone());
+            report.files.len()
+        });
+        let per_guid = time_in_memory(|| match_indices(&files, &mapping));
+        println!(
+            "{:>8} {:>12.3?} {:>12.3?}",
+            mapping.len(),
+            automaton,
+            per_guid
+        );
+    }
+
+    let dry_run = time(&root, &[]);
+    let forced = time(&root, &["--force"]);
+    println!("dry run: {:>10.3?}", dry_run);
+    println!("forced:  {:>10.3?}", forced);
+
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+fn env_usize(name: &str, default: usize) -> usize {
+    std::env::var(name)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(default)
+}
+
+fn time(root: &Path, args: &[&str]) -> Duration {
+    let start = Instant::now();
+    let status = Command::new(env!("CARGO_BIN_EXE_unity-guid-rewriter"))
+        .args(args)
+        .current_dir(root)
+        .env("RUST_LOG", "error")
+        .status()
+        .unwrap();
+    assert!(status.success());
+    start.elapsed()
+}
+
+/// How long `search` takes, checking it found something.
+fn time_in_memory(search: impl Fn() -> usize) -> Duration {
+    let start = Instant::now();
+    assert!(search() > 0);
+    start.elapsed()
+}
+
+/// The files that held a mapped GUID, found the way rewrites used to: one
+/// `match_indices` call per GUID per file.
+fn match_indices(files: &[(PathBuf, Vec<u8>)], mapping: &GuidMapping) -> usize {
+    files
+        .par_iter()
+        .filter(|(_, contents)| {
+            let contents = std::str::from_utf8(contents).unwrap();
+            mapping
+                .entries()
+                .iter()
+                .map(|entry| contents.match_indices(&entry.src).count())
+                .sum::<usize>()
+                > 0
+        })
+        .count()
+}
+
+fn load(root: &Path) -> Vec<(PathBuf, Vec<u8>)> {
+    let mut files = Vec::new();
+    for dir in std::fs::read_dir(root.join("Assets")).unwrap() {
+        for file in std::fs::read_dir(dir.unwrap().path()).unwrap() {
+            let path = file.unwrap().path();
+            let contents = std::fs::read(&path).unwrap();
+            files.push((path, contents));
+        }
+    }
+    files
+}
+
+/// Writes the project, returning the GUIDs of its assets.
+fn generate(root: &Path, assets: usize, refs: usize) -> Vec<String> {
+    // xorshift, so every run benchmarks the same project.
+    let mut state = 0x2545f4914f6cdd1d_u64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+
+    let guids = (0..assets)
+        .map(|_| format!("{:016x}{:016x}", next(), next()))
+        .collect::<Vec<_>>();
+
+    for (i, guid) in guids.iter().enumerate() {
+        let dir = root.join("Assets").join(format!("{:03}", i % 100));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let meta = format!("fileFormatVersion: 2\nguid: {}\nPrefabImporter:\n", guid);
+        std::fs::write(dir.join(format!("{}.prefab.meta", i)), meta).unwrap();
+
+        let mut prefab = String::from("%YAML 1.1\n%TAG !u! tag:unity3d.com,2011:\n");
+        for r in 0..refs {
+            let target = &guids[next() as usize % guids.len()];
+            writeln!(
+                prefab,
+                "--- !u!114 &{}\nMonoBehaviour:\n  m_Script: {{fileID: 11500000, guid: {}, type: 3}}",
+                r + 1,
+                target
+            )
+            .unwrap();
+        }
+        std::fs::write(dir.join(format!("{}.prefab", i)), prefab).unwrap();
+    }
+
+    guids
+}
@@ -0,0 +1,151 @@
+//! Assembly definitions (.asmdef) and assembly definition references
+//! (.asmref), JSON files which name other assemblies as `"GUID:<guid>"` when
+//! "Use GUIDs" is enabled: in the `references` list of an .asmdef, and in the
+//! `reference` field of an .asmref.
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use rayon::prelude::*;
+use regex::Regex;
+use serde_json::Value;
+
+use crate::{walk::walk_files, Error, FileFilter};
+
+/// An assembly named by its GUID.
+#[derive(Clone, Debug)]
+pub struct AssemblyReference {
+    /// The .asmdef or .asmref file the reference is in.
+    pub path: PathBuf,
+    pub guid: String,
+}
+
+static GUID_REFERENCE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#""GUID:([0-9a-fA-F]{32})""#).unwrap());
+
+/// Whether `path` is an .asmdef or .asmref file.
+pub fn is_assembly_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext == "asmdef" || ext == "asmref")
+}
+
+/// Byte offsets of the GUIDs in the `references` and `reference` entries of
+/// `contents`, with the GUIDs found there.
+pub fn guid_offsets(contents: &str) -> Result<Vec<(usize, String)>, String> {
+    // Unity may write a byte order mark, which JSON does not allow.
+    let json = serde_json::from_str::<Value>(contents.trim_start_matches('\u{feff}'))
+        .map_err(|e| e.to_string())?;
+
+    let references = match &json["references"] {
+        Value::Array(references) => references.iter().collect(),
+        _ => Vec::new(),
+    };
+    let guids = references
+        .into_iter()
+        .chain(Some(&json["reference"]))
+        .filter_map(|reference| reference.as_str()?.strip_prefix("GUID:"))
+        .collect::<HashSet<_>>();
+
+    Ok(GUID_REFERENCE
+        .captures_iter(contents)
+        .filter_map(|captures| captures.get(1))
+        .filter(|guid| guids.contains(guid.as_str()))
+        .map(|guid| (guid.start(), guid.as_str().to_ascii_lowercase()))
+        .collect())
+}
+
+/// Every GUID reference in the assembly files under `root` kept by `filter`,
+/// in path order, and the files that could not be listed, read or parsed.
+pub fn find_references(root: &Path, filter: &FileFilter) -> (Vec<AssemblyReference>, Vec<Error>) {
+    let (files, mut errors) = walk_files(root, filter);
+    let results = files
+        .into_par_iter()
+        .filter(|path| is_assembly_file(path))
+        .map(|path| {
+            let contents = std::fs::read_to_string(&path).map_err(|source| Error::Read {
+                path: path.clone(),
+                source,
+            })?;
+            let guids = guid_offsets(&contents).map_err(|reason| Error::Parse {
+                path: path.clone(),
+                reason,
+            })?;
+            Ok(guids
+                .into_iter()
+                .map(|(_, guid)| AssemblyReference {
+                    path: path.clone(),
+                    guid,
+                })
+                .collect::<Vec<_>>())
+        })
+        .collect::<Vec<_>>();
+
+    let mut references = Vec::new();
+    for result in results {
+        match result {
+            Ok(mut found) => references.append(&mut found),
+            Err(e) => errors.push(e),
+        }
+    }
+
+    (references, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A: &str = "0123456789abcdef0123456789abcdef";
+    const B: &str = "fedcba9876543210fedcba9876543210";
+
+    #[test]
+    fn asmdef_references() {
+        let contents = format!(
+            "\u{feff}{{\n  \"name\": \"Game\",\n  \"references\": [\"GUID:{}\", \"Unity.Mathematics\"]\n}}\n",
+            A
+        );
+        assert_eq!(
+            guid_offsets(&contents).unwrap(),
+            [(contents.find(A).unwrap(), A.to_owned())]
+        );
+    }
+
+    #[test]
+    fn asmref_reference() {
+        let contents = format!("{{\n  \"reference\": \"GUID:{}\"\n}}\n", A);
+        assert_eq!(
+            guid_offsets(&contents).unwrap(),
+            [(contents.find(A).unwrap(), A.to_owned())]
+        );
+    }
+
+    #[test]
+    fn uppercase_guids_are_lowercased() {
+        let upper = A.to_ascii_uppercase();
+        let contents = format!("{{\"references\": [\"GUID:{}\"]}}", upper);
+        assert_eq!(
+            guid_offsets(&contents).unwrap(),
+            [(contents.find(&upper).unwrap(), A.to_owned())]
+        );
+    }
+
+    #[test]
+    fn guids_outside_references_are_left_alone() {
+        let contents = format!(
+            "{{\n  \"name\": \"GUID:{}\",\n  \"references\": [\"GUID:{}\"],\n  \"defineConstraints\": [\"GUID:{}\"]\n}}\n",
+            B, A, B
+        );
+        assert_eq!(
+            guid_offsets(&contents).unwrap(),
+            [(contents.find(A).unwrap(), A.to_owned())]
+        );
+    }
+
+    #[test]
+    fn invalid_json() {
+        assert!(guid_offsets("{\"references\": [").is_err());
+    }
+}
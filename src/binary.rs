@@ -0,0 +1,27 @@
+//! Files that are not text, which are never rewritten as text. Mapped GUIDs
+//! found in them are still reported, as hex or in the raw 16-byte form Unity
+//! serializes, so references that could not be updated don't go unnoticed.
+
+use crate::serialized;
+
+/// How much of a file is searched for NUL bytes, as git does.
+const SNIFF_LEN: usize = 8000;
+
+/// Whether `contents` must not be rewritten as text: a NUL byte near the
+/// start, a Unity binary serialization header, or invalid UTF-8.
+pub fn is_binary(contents: &[u8]) -> bool {
+    contents[..contents.len().min(SNIFF_LEN)].contains(&0)
+        || serialized::Header::parse(contents).is_some()
+        || std::str::from_utf8(contents).is_err()
+}
+
+/// The 16 bytes Unity serializes for the GUID with hex digits `hex`: each
+/// byte holds two digits, low nibble first.
+pub fn raw_guid(hex: &str) -> [u8; 16] {
+    let digit = |c: u8| (c as char).to_digit(16).unwrap() as u8;
+    let mut raw = [0; 16];
+    for (byte, pair) in raw.iter_mut().zip(hex.as_bytes().chunks_exact(2)) {
+        *byte = digit(pair[1]) << 4 | digit(pair[0]);
+    }
+    raw
+}
@@ -0,0 +1,111 @@
+//! Consistency between assets and their .meta files, which Unity keeps in
+//! lockstep: every asset and folder has a .meta beside it holding its GUID,
+//! and a .meta without its asset is deleted on the next import.
+
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+use uuid::Uuid;
+use walkdir::WalkDir;
+
+use crate::{meta_path, read_meta_guid, staging, walk::unity_hidden, Error};
+
+/// What is wrong with the .meta files under a folder.
+#[derive(Debug, Default)]
+pub struct MetaCheck {
+    /// .meta files whose asset does not exist.
+    pub orphans: Vec<PathBuf>,
+    /// Assets and folders without a .meta.
+    pub missing: Vec<PathBuf>,
+    /// .meta files whose GUID is missing or malformed.
+    pub invalid: Vec<Error>,
+}
+
+impl MetaCheck {
+    /// Checks every asset under `dir`, which is usually a project's `Assets`
+    /// folder, skipping what Unity does not import. Also returns the entries
+    /// that could not be listed.
+    pub fn run(dir: &Path) -> (Self, Vec<Error>) {
+        let mut paths = BTreeSet::new();
+        let mut errors = Vec::new();
+
+        let entries = WalkDir::new(dir)
+            .min_depth(1)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| !unity_hidden(entry.file_name()));
+        for entry in entries {
+            match entry {
+                Ok(entry) => {
+                    paths.insert(entry.into_path());
+                }
+                Err(e) => errors.push(Error::Walk {
+                    path: e.path().unwrap_or(dir).to_owned(),
+                    source: e.into(),
+                }),
+            }
+        }
+
+        let mut check = Self::default();
+        for path in &paths {
+            match path.to_string_lossy().strip_suffix(".meta") {
+                Some(asset) if !paths.contains(Path::new(asset)) => {
+                    check.orphans.push(path.clone());
+                }
+                Some(_) => {
+                    if let Err(e) = read_meta_guid(path) {
+                        check.invalid.push(e);
+                    }
+                }
+                None if !paths.contains(&meta_path(path)) => check.missing.push(path.clone()),
+                None => {}
+            }
+        }
+
+        (check, errors)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orphans.is_empty() && self.missing.is_empty() && self.invalid.is_empty()
+    }
+}
+
+/// Deletes a .meta file whose asset is gone.
+pub fn remove_orphan(meta: &Path) -> Result<(), Error> {
+    std::fs::remove_file(meta).map_err(|source| Error::Write {
+        path: meta.to_owned(),
+        source,
+    })
+}
+
+/// Writes a .meta giving `guid` to the asset or folder at `asset`. Unity
+/// replaces the default importer with the right one on the next import.
+pub fn write_meta(asset: &Path, guid: &Uuid) -> Result<PathBuf, Error> {
+    let meta = meta_path(asset);
+    let folder = if asset.is_dir() {
+        "folderAsset: yes\n"
+    } else {
+        ""
+    };
+    let contents = format!(
+        "fileFormatVersion: 2\n\
+         guid: {}\n\
+         {}DefaultImporter:\n  \
+         externalObjects: {{}}\n  \
+         userData: \n  \
+         assetBundleName: \n  \
+         assetBundleVariant: \n",
+        guid.simple(),
+        folder
+    );
+
+    let write = |source| Error::Write {
+        path: meta.clone(),
+        source,
+    };
+    let temp = staging::stage(&meta, contents.as_bytes()).map_err(write)?;
+    staging::commit(&temp, &meta).map_err(write)?;
+    Ok(meta)
+}
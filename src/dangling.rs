@@ -0,0 +1,123 @@
+//! References to GUIDs no asset has: what Unity shows as a missing script,
+//! material or prefab once the asset they pointed at is deleted, or was never
+//! committed.
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use rayon::prelude::*;
+use uuid::Uuid;
+
+use crate::{
+    read_metas, walk::walk_files, yaml, Error, FileFilter, MetaFilter, ProtectedGuids, WalkMode,
+};
+
+/// Where a project's packages keep their assets: embedded packages under
+/// `Packages`, and downloaded ones under `Library/PackageCache`.
+pub const PACKAGE_DIRS: &[&str] = &["Packages", "Library/PackageCache"];
+
+/// The GUIDs of the .meta files under `dirs` visited in `mode`, and of every
+/// package of `project`, which are read whatever `mode` and ignore files say.
+/// Also returns the .meta files that could not be read.
+pub fn defined_guids(
+    project: &Path,
+    dirs: &[PathBuf],
+    mode: WalkMode,
+) -> (HashSet<Uuid>, Vec<Error>) {
+    let (metas, mut errors) = read_metas(dirs, &MetaFilter::default(), mode);
+
+    let packages = PACKAGE_DIRS
+        .iter()
+        .map(|dir| project.join(dir))
+        .filter(|dir| dir.is_dir())
+        // Already read in full.
+        .filter(|dir| mode != WalkMode::All || !dirs.iter().any(|d| dir.starts_with(d)))
+        .collect::<Vec<_>>();
+    let (packages, mut package_errors) =
+        read_metas(&packages, &MetaFilter::default(), WalkMode::All);
+    errors.append(&mut package_errors);
+
+    let guids = metas
+        .into_iter()
+        .chain(packages)
+        .map(|meta| meta.guid)
+        .collect();
+    (guids, errors)
+}
+
+/// A `guid:` reference that resolves to no asset.
+#[derive(Clone, Debug)]
+pub struct DanglingReference {
+    pub path: PathBuf,
+    /// The line the GUID is on, starting at 1.
+    pub line: usize,
+    /// The fileID of the object holding the reference, if it is in a
+    /// document with a `--- !u!<class> &<fileID>` header.
+    pub file_id: Option<String>,
+    pub guid: String,
+}
+
+/// Every reference in the Unity YAML and .meta files under `root` kept by
+/// `filter` whose GUID is neither `defined` nor `protected`, in path order.
+/// Also returns the files that could not be read or parsed.
+pub fn find(
+    root: &Path,
+    filter: &FileFilter,
+    defined: &HashSet<Uuid>,
+    protected: &ProtectedGuids,
+) -> (Vec<DanglingReference>, Vec<Error>) {
+    let (files, mut errors) = walk_files(root, filter);
+    let results = files
+        .into_par_iter()
+        .map(|path| {
+            let references = references(&path)?;
+            Ok(references
+                .into_iter()
+                .filter(|reference| {
+                    Uuid::parse_str(&reference.guid).map_or(true, |guid| {
+                        !defined.contains(&guid) && !protected.contains(&guid)
+                    })
+                })
+                .map(|reference| DanglingReference {
+                    path: path.clone(),
+                    line: reference.line,
+                    file_id: reference.file_id,
+                    guid: reference.guid,
+                })
+                .collect::<Vec<_>>())
+        })
+        .collect::<Vec<_>>();
+
+    let mut dangling = Vec::new();
+    for result in results {
+        match result {
+            Ok(mut references) => dangling.append(&mut references),
+            Err(e) => errors.push(e),
+        }
+    }
+
+    (dangling, errors)
+}
+
+/// The references in the file at `path`, or none if it isn't Unity YAML.
+fn references(path: &Path) -> Result<Vec<yaml::Reference>, Error> {
+    let contents = std::fs::read(path).map_err(|source| Error::Read {
+        path: path.to_owned(),
+        source,
+    })?;
+
+    // A .meta's own `guid:` is its asset's, not a reference.
+    let is_meta = path.extension().is_some_and(|ext| ext == "meta");
+    if !is_meta && !yaml::is_unity_yaml(&contents) {
+        return Ok(Vec::new());
+    }
+
+    let parse = |reason| Error::Parse {
+        path: path.to_owned(),
+        reason,
+    };
+    let contents = std::str::from_utf8(&contents).map_err(|e| parse(e.to_string()))?;
+    yaml::references(contents).map_err(parse)
+}
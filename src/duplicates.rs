@@ -0,0 +1,133 @@
+//! .meta files sharing a GUID, the classic result of copying a folder outside
+//! of Unity, and splitting them so every copy has a GUID of its own.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use uuid::Uuid;
+
+use crate::{relative_path, GuidMapping, GuidSource, MappingEntry, Meta};
+
+pub struct Duplicate {
+    pub guid: Uuid,
+    /// Every .meta claiming `guid`, shortest path first. Copies usually get
+    /// longer names, e.g. `A copy` or `x 1.png`, so the first one is treated
+    /// as the original and keeps the GUID.
+    pub metas: Vec<PathBuf>,
+}
+
+pub fn find(metas: &[Meta]) -> Vec<Duplicate> {
+    let mut by_guid = BTreeMap::<_, Vec<_>>::new();
+    for meta in metas {
+        by_guid
+            .entry(meta.guid)
+            .or_default()
+            .push(meta.path.clone());
+    }
+
+    by_guid
+        .into_iter()
+        .filter(|(_, metas)| metas.len() > 1)
+        .map(|(guid, mut metas)| {
+            metas.sort_by(|a, b| {
+                let len = |path: &PathBuf| path.as_os_str().len();
+                len(a).cmp(&len(b)).then_with(|| a.cmp(b))
+            });
+            Duplicate { guid, metas }
+        })
+        .collect()
+}
+
+/// The folder `copy` was pasted as: its path minus the trailing components it
+/// shares with `original`, so `Assets/A copy/Sub/x.png.meta` of
+/// `Assets/A/Sub/x.png.meta` gives `Assets/A copy`.
+///
+/// If that folder also holds the original, nothing but the copy's own .meta
+/// can safely be attributed to it, so the .meta itself is returned.
+pub fn copy_root(original: &Path, copy: &Path) -> PathBuf {
+    let original_components = original.components().rev();
+    let shared = copy
+        .components()
+        .rev()
+        .zip(original_components)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut root = copy.to_owned();
+    for _ in 0..shared.max(1) {
+        root.pop();
+    }
+
+    if original.starts_with(&root) {
+        copy.to_owned()
+    } else {
+        root
+    }
+}
+
+/// Mapping entries giving every copy a new GUID, each limited to the folder
+/// the copy was pasted as. The first .meta of every duplicate keeps its GUID.
+pub fn fix_mapping(root: &Path, duplicates: &[Duplicate], guids: &GuidSource) -> GuidMapping {
+    let mut mapping = GuidMapping::new();
+
+    for duplicate in duplicates {
+        let (original, copies) = duplicate.metas.split_first().unwrap();
+        for copy in copies {
+            let new_guid = guids.new_copy_guid(&duplicate.guid, &relative_path(root, copy));
+            mapping.push(MappingEntry {
+                meta: Some(copy.clone()),
+                scope: Some(copy_root(original, copy)),
+                ..MappingEntry::new(&duplicate.guid, &new_guid)
+            });
+        }
+    }
+
+    mapping
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn root(original: &str, copy: &str) -> PathBuf {
+        copy_root(Path::new(original), Path::new(copy))
+    }
+
+    #[test]
+    fn copied_folder() {
+        assert_eq!(
+            root("Assets/A/Sub/x.png.meta", "Assets/A copy/Sub/x.png.meta"),
+            Path::new("Assets/A copy")
+        );
+        assert_eq!(
+            root("Assets/A.meta", "Assets/A copy.meta"),
+            Path::new("Assets/A copy.meta")
+        );
+    }
+
+    #[test]
+    fn sibling_copy_is_only_its_meta() {
+        assert_eq!(
+            root("Assets/x.png.meta", "Assets/x 1.png.meta"),
+            Path::new("Assets/x 1.png.meta")
+        );
+    }
+
+    #[test]
+    fn nested_copies() {
+        assert_eq!(
+            root("Assets/A/Sub/x.meta", "Assets/A/Sub copy/x.meta"),
+            Path::new("Assets/A/Sub copy")
+        );
+        assert_eq!(
+            root("Assets/A/x.meta", "Assets/A/A copy/x.meta"),
+            Path::new("Assets/A/A copy")
+        );
+        assert_eq!(
+            root("Assets/A/B/x.meta", "Assets/A copy/B copy/x.meta"),
+            Path::new("Assets/A copy/B copy")
+        );
+    }
+}
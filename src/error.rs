@@ -0,0 +1,89 @@
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug)]
+pub enum Error {
+    /// A file could not be read.
+    Read { path: PathBuf, source: io::Error },
+    /// A .meta file is not the YAML mapping Unity writes.
+    MalformedMeta { path: PathBuf, reason: String },
+    /// A .meta file's `guid` is not a GUID.
+    InvalidGuid { path: PathBuf, guid: String },
+    /// A serialized file could not be parsed.
+    Parse { path: PathBuf, reason: String },
+    /// A file is not in the state the operation expected, e.g. it was edited
+    /// after a plan or journal was written.
+    Conflict { path: PathBuf, reason: String },
+    /// A file could not be written.
+    Write { path: PathBuf, source: io::Error },
+    /// A directory could not be listed.
+    Walk { path: PathBuf, source: io::Error },
+    /// A forced run wrote nothing because some files under `path` could not
+    /// be searched.
+    Incomplete { path: PathBuf, errors: Vec<Error> },
+}
+
+impl Error {
+    /// The file the error is about.
+    pub fn path(&self) -> &Path {
+        match self {
+            Error::Read { path, .. }
+            | Error::MalformedMeta { path, .. }
+            | Error::InvalidGuid { path, .. }
+            | Error::Parse { path, .. }
+            | Error::Conflict { path, .. }
+            | Error::Write { path, .. }
+            | Error::Walk { path, .. }
+            | Error::Incomplete { path, .. } => path,
+        }
+    }
+
+    /// A stable name for the variant, for machine-readable reports.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Read { .. } => "read",
+            Error::MalformedMeta { .. } => "malformed_meta",
+            Error::InvalidGuid { .. } => "invalid_guid",
+            Error::Parse { .. } => "parse",
+            Error::Conflict { .. } => "conflict",
+            Error::Write { .. } => "write",
+            Error::Walk { .. } => "walk",
+            Error::Incomplete { .. } => "incomplete",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Read { path, source } => write!(f, "reading {}: {}", path.display(), source),
+            Error::MalformedMeta { path, reason } => write!(f, "{}: {}", path.display(), reason),
+            Error::InvalidGuid { path, guid } => {
+                write!(f, "{}: invalid guid {:?}", path.display(), guid)
+            }
+            Error::Parse { path, reason } => write!(f, "parsing {}: {}", path.display(), reason),
+            Error::Conflict { path, reason } => write!(f, "{}: {}", path.display(), reason),
+            Error::Write { path, source } => write!(f, "writing {}: {}", path.display(), source),
+            Error::Walk { path, source } => write!(f, "listing {}: {}", path.display(), source),
+            Error::Incomplete { path, errors } => write!(
+                f,
+                "{}: {} files could not be searched, nothing was written",
+                path.display(),
+                errors.len()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Read { source, .. }
+            | Error::Write { source, .. }
+            | Error::Walk { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,267 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    binary, is_simple_guid, relative_path, sha256, staging, Error, FileReport, GuidMapping,
+};
+
+/// Every file a forced run rewrote, with enough detail to put the original
+/// GUIDs back byte for byte.
+#[derive(Serialize, Deserialize)]
+pub struct Journal {
+    pub files: Vec<JournaledFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct JournaledFile {
+    pub path: String,
+    pub original_sha256: String,
+    pub rewritten_sha256: String,
+    /// Whether GUIDs were rewritten as raw bytes in a binary file.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub binary: bool,
+    pub rewrites: Vec<Rewrite>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Rewrite {
+    pub src: String,
+    pub dst: String,
+    pub offsets: Vec<usize>,
+}
+
+impl Journal {
+    pub fn new(root: &Path, mapping: &GuidMapping, files: &[FileReport]) -> Self {
+        let files = files
+            .iter()
+            .filter_map(|file| {
+                Some(JournaledFile {
+                    path: relative_path(root, &file.path),
+                    original_sha256: file.sha256.clone(),
+                    rewritten_sha256: file.rewritten_sha256.clone()?,
+                    binary: file.binary,
+                    rewrites: file
+                        .hits
+                        .iter()
+                        .map(|(n, offsets)| Rewrite {
+                            src: mapping[*n].src.clone(),
+                            dst: mapping[*n].dst.clone(),
+                            offsets: offsets.clone(),
+                        })
+                        .collect(),
+                })
+            })
+            .collect();
+
+        Self { files }
+    }
+
+    /// Reads a journal, rejecting rewrites that aren't between two GUIDs,
+    /// since it may have been edited or truncated.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let mut journal = crate::load_json::<Self>(path)?;
+
+        let rewrites = journal.files.iter_mut().flat_map(|file| &mut file.rewrites);
+        for guid in rewrites.flat_map(|rewrite| [&mut rewrite.src, &mut rewrite.dst]) {
+            if !is_simple_guid(guid) {
+                return Err(Error::InvalidGuid {
+                    path: path.to_owned(),
+                    guid: guid.clone(),
+                });
+            }
+            guid.make_ascii_lowercase();
+        }
+
+        Ok(journal)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        crate::save_json(path, self)
+    }
+
+    /// Restores every journaled file under `root`. Nothing is written unless
+    /// all files are exactly as the journaled run left them, or already
+    /// restored, e.g. because that run was interrupted. Returns how many files
+    /// were restored.
+    pub fn revert(&self, root: &Path) -> Result<usize, Vec<Error>> {
+        let mut staged = Vec::new();
+        let mut errors = Vec::new();
+
+        for file in &self.files {
+            let path = root.join(&file.path);
+            match file.restore(&path) {
+                Ok(Some(contents)) => match staging::stage(&path, &contents) {
+                    Ok(temp) => staged.push((temp, path)),
+                    Err(source) => errors.push(Error::Write { path, source }),
+                },
+                Ok(None) => log::info!("{} is already reverted", file.path),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        if !errors.is_empty() {
+            for (temp, _) in &staged {
+                staging::discard(temp);
+            }
+            return Err(errors);
+        }
+
+        for (_, path) in &staged {
+            log::info!("reverting {}", path.display());
+        }
+
+        staging::commit_all(
+            staged
+                .iter()
+                .map(|(temp, path)| (temp.as_path(), path.as_path())),
+        )
+        .map_err(|e| vec![e])
+    }
+}
+
+impl JournaledFile {
+    fn restore(&self, path: &Path) -> Result<Option<Vec<u8>>, Error> {
+        let conflict = |reason: String| Error::Conflict {
+            path: path.to_owned(),
+            reason,
+        };
+
+        let mut contents = std::fs::read(path).map_err(|source| Error::Read {
+            path: path.to_owned(),
+            source,
+        })?;
+        let hash = sha256(&contents);
+        if hash == self.original_sha256 {
+            return Ok(None);
+        }
+        if hash != self.rewritten_sha256 {
+            return Err(conflict("modified since it was rewritten".to_owned()));
+        }
+
+        for rewrite in &self.rewrites {
+            let (src, dst) = if self.binary {
+                (
+                    binary::raw_guid(&rewrite.src).to_vec(),
+                    binary::raw_guid(&rewrite.dst).to_vec(),
+                )
+            } else {
+                (
+                    rewrite.src.clone().into_bytes(),
+                    rewrite.dst.clone().into_bytes(),
+                )
+            };
+
+            for &n in &rewrite.offsets {
+                let guid = contents
+                    .get_mut(n..n + dst.len())
+                    .filter(|guid| **guid == *dst)
+                    .ok_or_else(|| conflict(format!("expected {} at offset {}", rewrite.dst, n)))?;
+                guid.copy_from_slice(&src);
+            }
+        }
+
+        if sha256(&contents) != self.original_sha256 {
+            return Err(conflict(
+                "restored contents do not match the original".to_owned(),
+            ));
+        }
+
+        Ok(Some(contents))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rewriter;
+
+    const A: &str = "0123456789abcdef0123456789abcdef";
+    const B: &str = "fedcba9876543210fedcba9876543210";
+
+    /// Rewrites `A` to `B` in `files` under a new project, returning it with
+    /// the journal of the run.
+    fn rewritten(files: &[(&str, String)]) -> (tempfile::TempDir, Journal) {
+        let root = tempfile::tempdir().unwrap();
+        for (path, contents) in files {
+            std::fs::write(root.path().join(path), contents).unwrap();
+        }
+
+        let mapping = GuidMapping::from_pairs([(A.to_owned(), B.to_owned())]);
+        let journal = root.path().join("journal.json");
+        Rewriter::new(&mapping)
+            .force(true)
+            .journal(&journal)
+            .run(root.path())
+            .unwrap();
+
+        let journal = Journal::load(&journal).unwrap();
+        (root, journal)
+    }
+
+    fn read(root: &tempfile::TempDir, path: &str) -> String {
+        std::fs::read_to_string(root.path().join(path)).unwrap()
+    }
+
+    #[test]
+    fn clean_revert() {
+        let original = format!("guid: {}\n", A);
+        let (root, journal) = rewritten(&[("a.mat", original.clone())]);
+        assert_eq!(read(&root, "a.mat"), format!("guid: {}\n", B));
+
+        assert_eq!(journal.revert(root.path()).unwrap(), 1);
+        assert_eq!(read(&root, "a.mat"), original);
+    }
+
+    #[test]
+    fn already_reverted() {
+        let (root, journal) = rewritten(&[("a.mat", format!("guid: {}\n", A))]);
+        journal.revert(root.path()).unwrap();
+
+        assert_eq!(journal.revert(root.path()).unwrap(), 0);
+        assert_eq!(read(&root, "a.mat"), format!("guid: {}\n", A));
+    }
+
+    #[test]
+    fn modified_file_is_a_conflict() {
+        let (root, journal) = rewritten(&[
+            ("a.mat", format!("guid: {}\n", A)),
+            ("b.mat", format!("guid: {}\n", A)),
+        ]);
+        let edited = format!("guid: {}\nedited: 1\n", B);
+        std::fs::write(root.path().join("b.mat"), &edited).unwrap();
+
+        let errors = journal.revert(root.path()).unwrap_err();
+        assert!(matches!(errors[..], [Error::Conflict { .. }]));
+        assert_eq!(read(&root, "a.mat"), format!("guid: {}\n", B));
+        assert_eq!(read(&root, "b.mat"), edited);
+    }
+
+    #[test]
+    fn binary_revert() {
+        let root = tempfile::tempdir().unwrap();
+        let file = |guid: &str| [&[0, 1, 2, 3][..], &binary::raw_guid(guid), &[4, 5]].concat();
+        let (original, rewritten) = (file(A), file(B));
+        std::fs::write(root.path().join("a.asset"), &rewritten).unwrap();
+
+        let journal = Journal {
+            files: vec![JournaledFile {
+                path: "a.asset".to_owned(),
+                original_sha256: sha256(&original),
+                rewritten_sha256: sha256(&rewritten),
+                binary: true,
+                rewrites: vec![Rewrite {
+                    src: A.to_owned(),
+                    dst: B.to_owned(),
+                    offsets: vec![4],
+                }],
+            }],
+        };
+
+        assert_eq!(journal.revert(root.path()).unwrap(), 1);
+        assert_eq!(
+            std::fs::read(root.path().join("a.asset")).unwrap(),
+            original
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! Gives Unity assets new GUIDs and rewrites every reference to them.
+//!
+//! [`read_metas`] collects the GUIDs of a set of assets,
+//! [`GuidMapping::generate`] pairs each of them with a new one, leaving
+//! Unity's built-in GUIDs alone, and a
+//! [`Rewriter`] applies the mapping to a project:
+//!
+//! ```no_run
+//! use std::path::Path;
+//!
+//! use unity_guid_rewriter::{
+//!     read_metas, FileFilter, GuidMapping, GuidSource, MetaFilter, ProtectedGuids, Rewriter,
+//!     WalkMode,
+//! };
+//!
+//! # fn main() -> Result<(), unity_guid_rewriter::Error> {
+//! let dirs = ["MyProject/Assets/Vendor".into()];
+//! let (metas, _errors) = read_metas(&dirs, &MetaFilter::default(), WalkMode::Unity);
+//! let mapping = GuidMapping::generate(&metas, &GuidSource::Random, &ProtectedGuids::default());
+//! let report = Rewriter::new(&mapping)
+//!     .files(FileFilter::with_default_exclude(vec![], vec![]).unwrap().walk(WalkMode::Unity))
+//!     .force(true)
+//!     .run(Path::new("MyProject"))?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod asmdef;
+mod binary;
+pub mod check;
+pub mod dangling;
+pub mod duplicates;
+mod error;
+pub mod journal;
+mod mapping;
+pub mod merge;
+mod meta;
+pub mod package;
+pub mod plan;
+mod protected;
+pub mod report;
+mod rewrite;
+pub mod serialized;
+mod staging;
+pub mod target;
+mod walk;
+mod yaml;
+
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+pub use error::Error;
+pub use journal::Journal;
+pub use mapping::{GuidMapping, GuidSource, MappingEntry};
+pub use meta::{parse_meta_guid, read_meta_guid, read_metas, Meta, MetaFilter};
+pub use plan::RewritePlan;
+pub use protected::{ProtectedGuids, BUILTIN_GUIDS};
+pub use rewrite::{FileReport, MatchOptions, RewriteMode, RewriteReport, Rewriter};
+pub use walk::{FileFilter, WalkMode, DEFAULT_EXCLUDE};
+
+const UUID_STR_LEN: usize = 32;
+
+/// Whether `guid` is written the way Unity writes GUIDs: 32 hex digits, with
+/// no hyphens or braces.
+fn is_simple_guid(guid: &str) -> bool {
+    guid.len() == UUID_STR_LEN && guid.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// `path` relative to `root`, always `/`-separated so plans and journals are
+/// portable. Paths outside `root` are returned as they are.
+pub fn relative_path(root: &Path, path: &Path) -> String {
+    let Ok(path) = path.strip_prefix(root) else {
+        return path.to_string_lossy().into_owned();
+    };
+    path.iter()
+        .map(|c| c.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// The .meta file of the asset at `asset`.
+fn meta_path(asset: &Path) -> PathBuf {
+    let mut path = asset.as_os_str().to_owned();
+    path.push(".meta");
+    path.into()
+}
+
+fn sha256(contents: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(contents))
+}
+
+fn load_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T, Error> {
+    let read = |source| Error::Read {
+        path: path.to_owned(),
+        source,
+    };
+
+    let file = std::fs::File::open(path).map_err(read)?;
+    serde_json::from_reader(std::io::BufReader::new(file)).map_err(|e| read(e.into()))
+}
+
+fn save_json<T: serde::Serialize>(path: &Path, value: &T) -> Result<(), Error> {
+    let write = |source| Error::Write {
+        path: path.to_owned(),
+        source,
+    };
+
+    let file = std::fs::File::create(path).map_err(write)?;
+    serde_json::to_writer_pretty(std::io::BufWriter::new(file), value).map_err(|e| write(e.into()))
+}
@@ -1,24 +1,331 @@
 use std::{
-    borrow::Cow,
-    path::{Path, PathBuf},
+    collections::HashSet,
+    path::{Component, Path, PathBuf},
 };
 
-use clap::Parser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use globset::{Glob, GlobBuilder};
+use unity_guid_rewriter::{
+    asmdef,
+    check::{self, MetaCheck},
+    dangling, duplicates,
+    merge::Merge,
+    package::Package,
+    read_metas, relative_path,
+    report::{
+        CollisionRecord, ErrorRecord, Event, FileRecord, MappingRecord, Report, UnresolvedRecord,
+    },
+    target::Target,
+    Error, FileFilter, FileReport, GuidMapping, GuidSource, Journal, MatchOptions, Meta,
+    MetaFilter, ProtectedGuids, RewriteMode, RewritePlan, RewriteReport, Rewriter, WalkMode,
+};
 use uuid::Uuid;
-use walkdir::WalkDir;
-use yaml_rust::{Yaml, YamlLoader};
 
-const UUID_STR_LEN: usize = 32;
+/// Exit statuses, distinct so CI can tell what went wrong. clap itself exits
+/// with 2 on invalid arguments.
+const EXIT_FAILURE: i32 = 1;
+const EXIT_INCOMPLETE: i32 = 3;
+const EXIT_STRICT: i32 = 4;
+const EXIT_CHANGED: i32 = 5;
+const EXIT_DUPLICATES: i32 = 6;
+const EXIT_MISMATCHED: i32 = 7;
+const EXIT_DANGLING: i32 = 8;
+
+const EXIT_STATUS_HELP: &str = "\
+Exit status:
+  0  success
+  1  nothing was written, or a write failed part way
+  2  invalid arguments
+  3  finished, but some files could not be read or parsed
+  4  --strict and some .meta files could not be read; nothing was written
+  5  the project changed since the plan or journal was written
+  6  GUIDs are shared by several .meta files
+  7  .meta files and assets don't match up, or a .meta has no valid GUID
+  8  files reference GUIDs that no .meta defines";
 
 #[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true, after_help = EXIT_STATUS_HELP)]
 struct Options {
     #[arg(long, short)]
     force: bool,
-    #[arg(long, short)]
+    /// Number of worker threads, defaults to one per CPU
+    #[arg(long, short, global = true)]
+    jobs: Option<usize>,
+    /// Root of the project whose files are rewritten, defaults to the current
+    /// directory
+    #[arg(long, global = true)]
+    project: Option<PathBuf>,
+    /// Record every rewritten file here so the run can be reverted. Keep it
+    /// outside the project, or later runs will rewrite the GUIDs it names
+    #[arg(long)]
+    journal: Option<PathBuf>,
+    /// Write the files that could be rewritten even if others could not be
+    /// read or parsed, which then keep the old GUIDs
+    #[arg(long, global = true)]
+    partial: bool,
+    /// Describe the mapping, every touched file and every failure on stdout
+    #[arg(long, value_enum, global = true, value_name = "FORMAT")]
+    report: Option<ReportFormat>,
+    #[command(flatten)]
+    scan: ScanArgs,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Args)]
+struct ScanArgs {
+    /// Deprecated, use --exclude: comma-separated file extensions to leave
+    /// alone, e.g. `png,fbx`
+    #[arg(long, short, value_name = "EXTENSIONS")]
     ignore: Option<String>,
+    /// Only rewrite files matching this gitignore-style pattern, relative to
+    /// the project, e.g. `Assets/`; may be repeated
+    #[arg(long, value_name = "PATTERN")]
+    include: Vec<String>,
+    /// Leave files matching this gitignore-style pattern alone, e.g. `*.asset`
+    /// or `/Assets/Plugins/`; may be repeated. Version control, IDE and editor
+    /// folders and common binary formats are excluded by default
+    #[arg(long, value_name = "PATTERN")]
+    exclude: Vec<String>,
+    /// Don't exclude anything by default
+    #[arg(long)]
+    no_default_exclude: bool,
+    /// Which files and .meta files are visited before --include and
+    /// --exclude apply
+    #[arg(long, value_enum, default_value_t = WalkMode::All)]
+    walk: WalkMode,
+    #[command(flatten)]
+    matching: MatchArgs,
+    /// Abort before writing anything if any .meta file could not be read
+    #[arg(long)]
+    strict: bool,
+    #[command(flatten)]
+    mapping: MappingArgs,
+    /// Directory to collect .meta files from, inside or next to the project;
+    /// may be repeated
+    #[arg(long = "scan", value_name = "DIR")]
+    scan_dirs: Vec<PathBuf>,
     scan_dir: Option<PathBuf>,
 }
 
+#[derive(Args)]
+struct MatchArgs {
+    /// Only rewrite GUIDs that follow a `guid:`-style key, such as `guid: `,
+    /// `"GUID:` in assembly definitions or `m_AssetGUID: `
+    #[arg(long)]
+    guid_context: bool,
+    /// How files are searched for GUIDs to rewrite
+    #[arg(long, value_enum, default_value_t = RewriteMode::Text)]
+    mode: RewriteMode,
+}
+
+/// Which GUIDs are remapped, and to what.
+#[derive(Args)]
+struct MappingArgs {
+    #[command(flatten)]
+    guids: GuidArgs,
+    /// Only remap the GUIDs of .meta files matching this glob, relative to
+    /// the scan directory, e.g. `Assets/Vendor/**`; may be repeated
+    #[arg(long, value_name = "GLOB", value_parser = parse_glob)]
+    only: Vec<Glob>,
+    /// Never remap the GUIDs of .meta files matching this glob, e.g.
+    /// `**/*.shader.meta`; may be repeated
+    #[arg(long, value_name = "GLOB", value_parser = parse_glob)]
+    except: Vec<Glob>,
+    #[command(flatten)]
+    protect: ProtectArgs,
+    /// Only remap GUIDs that another asset of this project already uses,
+    /// e.g. the project a package or folder is about to be merged into
+    #[arg(long, value_name = "PROJECT")]
+    against: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct GuidArgs {
+    /// Derive new GUIDs from this seed, so every run produces the same ones
+    #[arg(long, conflicts_with = "namespace")]
+    seed: Option<String>,
+    /// Derive each new GUID as the UUIDv5 of this namespace and the old GUID
+    #[arg(long)]
+    namespace: Option<Uuid>,
+}
+
+/// GUIDs never remapped besides Unity's built-in ones.
+#[derive(Args)]
+struct ProtectArgs {
+    /// Never remap this GUID; may be repeated. Unity's built-in GUIDs are
+    /// always protected
+    #[arg(long, value_name = "GUID")]
+    protect: Vec<Uuid>,
+    /// Never remap the GUIDs listed in this file, one per line
+    #[arg(long, value_name = "FILE")]
+    protect_file: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ReportFormat {
+    /// One JSON document, written when the run ends
+    Json,
+    /// One JSON object per line, written as the run goes
+    Jsonl,
+}
+
+struct Scan {
+    project: PathBuf,
+    dirs: Vec<PathBuf>,
+    strict: bool,
+    files: FileFilter,
+    matching: MatchOptions,
+    remapping: Remapping,
+}
+
+struct Remapping {
+    filter: MetaFilter,
+    guids: GuidSource,
+    protected: ProtectedGuids,
+    /// The project given with `--against`.
+    against: Option<Target>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Write the GUID mapping and the files it would touch to a plan file
+    Plan(PlanArgs),
+    /// Rewrite files using exactly the mapping from a plan file
+    Apply(ApplyArgs),
+    /// Give the assets of a .unitypackage new GUIDs, writing the result as a
+    /// new package
+    Package(PackageArgs),
+    /// Copy the assets of another project into this one, leaving out those
+    /// whose path is taken and giving new GUIDs to those whose GUID is
+    Merge(MergeArgs),
+    /// List .meta files without an asset, assets and folders without a .meta,
+    /// and .meta files without a valid GUID, exiting with an error if any
+    Check(CheckArgs),
+    /// Restore the original GUIDs in files rewritten by a journaled run
+    Revert(RevertArgs),
+    /// List GUIDs shared by several .meta files, exiting with an error if any
+    Duplicates(DuplicatesArgs),
+    /// List references to GUIDs that no .meta in the scan directories
+    /// defines, exiting with an error if any
+    Dangling(DanglingArgs),
+}
+
+#[derive(Args)]
+struct PlanArgs {
+    #[command(flatten)]
+    scan: ScanArgs,
+    /// Where to write the plan. Keep it outside the project, or later
+    /// runs will rewrite the GUIDs it names
+    #[arg(long, short)]
+    output: PathBuf,
+}
+
+#[derive(Args)]
+struct ApplyArgs {
+    #[arg(long)]
+    plan: PathBuf,
+    /// Record every rewritten file here so the run can be reverted. Keep it
+    /// outside the project, or later runs will rewrite the GUIDs it names
+    #[arg(long)]
+    journal: Option<PathBuf>,
+    #[command(flatten)]
+    protect: ProtectArgs,
+}
+
+#[derive(Args)]
+struct PackageArgs {
+    package: PathBuf,
+    #[arg(long, short)]
+    output: PathBuf,
+    #[arg(long, short)]
+    force: bool,
+    #[command(flatten)]
+    matching: MatchArgs,
+    #[command(flatten)]
+    mapping: MappingArgs,
+}
+
+#[derive(Args)]
+struct MergeArgs {
+    src: PathBuf,
+    dst: PathBuf,
+    /// Only copy files matching this gitignore-style pattern, relative to
+    /// the source project; may be repeated. Defaults to `/Assets/`
+    #[arg(long, value_name = "PATTERN")]
+    include: Vec<String>,
+    /// Don't copy files matching this gitignore-style pattern; may be
+    /// repeated
+    #[arg(long, value_name = "PATTERN")]
+    exclude: Vec<String>,
+    /// Which files are visited before --include and --exclude apply
+    #[arg(long, value_enum, default_value_t = WalkMode::All)]
+    walk: WalkMode,
+    #[arg(long, short)]
+    force: bool,
+    #[command(flatten)]
+    matching: MatchArgs,
+    #[command(flatten)]
+    guids: GuidArgs,
+    #[command(flatten)]
+    protect: ProtectArgs,
+}
+
+#[derive(Args)]
+struct CheckArgs {
+    /// Folders to check, defaults to the project's Assets folder
+    dirs: Vec<PathBuf>,
+    /// Delete .meta files without an asset, and give assets without a
+    /// .meta a new one with a fresh GUID
+    #[arg(long)]
+    fix: bool,
+    #[arg(long, short, requires = "fix")]
+    force: bool,
+    #[command(flatten)]
+    guids: GuidArgs,
+}
+
+#[derive(Args)]
+struct RevertArgs {
+    journal: PathBuf,
+}
+
+#[derive(Args)]
+struct DuplicatesArgs {
+    #[command(flatten)]
+    scan: ScanArgs,
+    /// Give every copy but the first a new GUID, rewriting references to
+    /// it inside the folder the copy was pasted as
+    #[arg(long)]
+    fix: bool,
+    #[arg(long, short, requires = "fix")]
+    force: bool,
+    /// Record every rewritten file here so the run can be reverted. Keep it
+    /// outside the project, or later runs will rewrite the GUIDs it names
+    #[arg(long, requires = "fix")]
+    journal: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct DanglingArgs {
+    #[command(flatten)]
+    scan: ScanArgs,
+}
+
+/// What every command runs with, from the global options.
+struct Context {
+    working_dir: PathBuf,
+    project: PathBuf,
+    partial: bool,
+}
+
+impl Context {
+    /// `path` resolved against the working directory.
+    fn absolute(&self, path: &Path) -> PathBuf {
+        absolute(&self.working_dir, path)
+    }
+}
+
 fn main() {
     env_logger::Builder::new()
         .filter_level(log::LevelFilter::Info)
@@ -26,154 +333,916 @@ fn main() {
         .init();
 
     let Options {
-        ignore,
-        scan_dir,
         force,
+        jobs,
+        project,
+        journal,
+        partial,
+        report,
+        scan,
+        command,
     } = Options::parse();
 
+    if let Some(jobs) = jobs {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build_global()
+            .unwrap();
+    }
+
     let working_dir = std::env::current_dir().unwrap();
-    let scan_dir = scan_dir.map_or(Cow::Borrowed(&working_dir), Cow::Owned);
-    let ignore = ignore
-        .map_or(Cow::Borrowed("png,git,fbx,exe"), Cow::Owned)
-        .split(",")
-        .map(|s| format!(".{}", s.trim()))
-        .collect::<Vec<_>>();
+    let project = project.map_or_else(|| working_dir.clone(), |p| absolute(&working_dir, &p));
+    require_dir("project", &project);
+    log::info!("project: {}", project.display());
 
-    let mapping = make_mapping(&scan_dir);
-    apply_mapping(&working_dir, &ignore, &mapping, force);
+    let context = Context {
+        working_dir,
+        project,
+        partial,
+    };
+    let mut output = Output::new(&context.project, report);
+
+    match command {
+        None => rewrite_project(&context, scan, force, journal, &mut output),
+        Some(Command::Plan(args)) => write_plan(&context, args, &mut output),
+        Some(Command::Apply(args)) => apply_plan(&context, args, &mut output),
+        Some(Command::Package(args)) => rewrite_package(&context, args, &mut output),
+        Some(Command::Merge(args)) => merge_projects(&context, args, &mut output),
+        Some(Command::Check(args)) => check_metas(&context, args, &mut output),
+        Some(Command::Revert(args)) => revert_journal(&context, args, &mut output),
+        Some(Command::Duplicates(args)) => find_duplicates(&context, args, &mut output),
+        Some(Command::Dangling(args)) => find_dangling(&context, args, &mut output),
+    }
+
+    output.finish();
+}
+
+/// Gives the GUIDs of the scanned .meta files new ones, in every file of the
+/// project.
+fn rewrite_project(
+    context: &Context,
+    scan: ScanArgs,
+    force: bool,
+    journal: Option<PathBuf>,
+    output: &mut Output,
+) {
+    let project = &context.project;
+    let scan = scan.resolve(&context.working_dir, project);
+    let mapping = make_mapping(&read(&scan, output), &scan.remapping, &scan.roots(project));
+    let rewriter = scan
+        .rewriter(&mapping)
+        .force(force)
+        .partial(context.partial);
+    let journal = journal
+        .filter(|_| force)
+        .map(|path| context.absolute(&path));
+    rewrite(rewriter, project, journal, &mapping, output);
+    check_references(&scan, project, &mapping, output);
 
     if !force {
         log::warn!("Dry-run: no changes made. Use --force or -f to apply changes.");
     }
 }
 
-fn make_mapping(dir: &Path) -> Vec<(String, String)> {
-    let mut mapping = Vec::new();
-    let guid_key = Yaml::String("guid".to_owned());
+fn write_plan(context: &Context, args: PlanArgs, output: &mut Output) {
+    let project = &context.project;
+    let plan_path = context.absolute(&args.output);
+    let scan = args.scan.resolve(&context.working_dir, project);
+    let mapping = make_mapping(&read(&scan, output), &scan.remapping, &scan.roots(project));
+    let rewriter = scan.rewriter(&mapping).skip(&plan_path);
+    let report = rewrite(rewriter, project, None, &mapping, output);
+    check_references(&scan, project, &mapping, output);
 
-    for entry in WalkDir::new(dir) {
-        let entry = entry.unwrap();
+    let plan = RewritePlan::new(project, &scan.files, scan.matching, &mapping, &report.files);
+    if let Err(e) = plan.save(&plan_path) {
+        output.fail(e);
+        output.exit(EXIT_FAILURE, "could not write the plan");
+    }
+    log::info!("wrote plan to {}", plan_path.display());
+}
 
-        if !entry.file_type().is_file() {
-            continue;
+fn apply_plan(context: &Context, args: ApplyArgs, output: &mut Output) {
+    let project = &context.project;
+    let plan_path = context.absolute(&args.plan);
+    let plan = match RewritePlan::load(&plan_path) {
+        Ok(plan) => plan,
+        Err(e) => {
+            output.fail(e);
+            output.exit(EXIT_FAILURE, "could not read the plan");
         }
+    };
 
-        let file_name = entry.file_name().to_string_lossy();
-        if !file_name.ends_with(".meta") {
-            continue;
+    let protected = plan.protected(&plan_path, &args.protect.resolve(&context.working_dir));
+    if !protected.is_empty() {
+        protected.into_iter().for_each(|e| output.fail(e));
+        output.exit(EXIT_FAILURE, "the plan remaps protected GUIDs");
+    }
+
+    let files = or_exit(
+        plan.file_filter()
+            .map_err(|e| format!("{}: {}", plan_path.display(), e)),
+    );
+
+    let mapping = plan.mapping();
+    let rewriter = Rewriter::new(&mapping)
+        .files(files)
+        .skip(&plan_path)
+        .matching(plan.matching);
+    let mut report = match rewriter.run(project) {
+        Ok(report) => report,
+        Err(e) => {
+            output.fail(e);
+            output.exit(EXIT_FAILURE, "could not search the project");
         }
+    };
+    if !report.errors.is_empty() {
+        // Otherwise a file that can't be read looks like one that
+        // lost its GUIDs.
+        std::mem::take(&mut report.errors)
+            .into_iter()
+            .for_each(|e| output.fail(e));
+        output.exit(EXIT_FAILURE, "could not search every file of the plan");
+    }
 
-        let yaml = match std::fs::read_to_string(entry.path()) {
-            Ok(yaml) => yaml,
-            Err(e) => {
-                log::error!("reading {}: {}", entry.path().display(), e);
-                continue;
-            }
-        };
+    let changes = plan.changes(project, &report.files);
+    if !changes.is_empty() {
+        changes.into_iter().for_each(|e| output.fail(e));
+        output.exit(
+            EXIT_CHANGED,
+            "tree changed since the plan was made, refusing to apply",
+        );
+    }
 
-        let yaml = match YamlLoader::load_from_str(&yaml) {
-            Ok(mut xs) if xs.len() == 1 => xs.pop().unwrap(),
-            Ok(xs) => {
-                log::error!(
-                    "unexpected {} documents in .meta: {}",
-                    xs.len(),
-                    entry.path().display()
-                );
-                continue;
-            }
-            Err(e) => {
-                log::error!("parsing {}: {}", entry.path().display(), e);
-                continue;
-            }
-        };
+    let journal = args.journal.map(|path| context.absolute(&path));
+    rewrite(
+        rewriter.force(true).partial(context.partial),
+        project,
+        journal,
+        &mapping,
+        output,
+    );
+}
 
-        let Yaml::Hash(hash) = yaml else {
-            log::error!("unexpected non-hash in .meta: {}", entry.path().display());
-            continue;
+fn rewrite_package(context: &Context, args: PackageArgs, output: &mut Output) {
+    let package_path = context.absolute(&args.package);
+    let output_path = context.absolute(&args.output);
+    let mut package = match Package::read(&package_path) {
+        Ok(package) => package,
+        Err(e) => {
+            output.fail(e);
+            output.exit(EXIT_FAILURE, "could not read the package");
+        }
+    };
+
+    let remapping = args.mapping.resolve(&context.working_dir);
+    let (mut metas, errors) = package.metas();
+    errors.into_iter().for_each(|e| output.fail(e));
+    metas.retain(|meta| remapping.filter.is_match(Path::new(""), &meta.path));
+    retain_unprotected(&mut metas, &remapping.protected);
+
+    // Package paths are already relative to the project.
+    let mapping = make_mapping(&metas, &remapping, &[]);
+    output.mapping(&mapping);
+    let report = package.rewrite(&mapping, args.matching.options(), args.force);
+    let incomplete = !report.errors.is_empty();
+    record(report, &mapping, output);
+
+    if args.force && !context.partial && incomplete {
+        output.exit(
+            EXIT_FAILURE,
+            "some files could not be searched, no package written; \
+             use --partial to write it anyway",
+        );
+    }
+    if args.force {
+        if let Err(e) = package.write(&output_path) {
+            output.fail(e);
+            output.exit(EXIT_FAILURE, "could not write the package");
+        }
+        log::info!("wrote package to {}", output_path.display());
+    } else {
+        log::warn!("Dry-run: no package written. Use --force or -f to write it.");
+    }
+}
+
+fn merge_projects(context: &Context, args: MergeArgs, output: &mut Output) {
+    let src = context.absolute(&args.src);
+    let dst = context.absolute(&args.dst);
+    require_dir("project", &src);
+    require_dir("project", &dst);
+    if src == dst {
+        log::error!("cannot merge {} into itself", src.display());
+        std::process::exit(EXIT_FAILURE);
+    }
+
+    let include = match args.include.is_empty() {
+        true => vec!["/Assets/".to_owned()],
+        false => args.include,
+    };
+    let files = or_exit(FileFilter::new(include, args.exclude)).walk(args.walk);
+
+    output.root = dst.clone();
+    let protected = args.protect.resolve(&context.working_dir);
+    let (merge, errors) = Merge::new(&src, &dst, &files, &args.guids.source(), &protected);
+    errors.into_iter().for_each(|e| output.fail(e));
+
+    for collision in &merge.collisions {
+        let what = match dst.join(&collision.path).is_dir() {
+            true => "merging into its folder",
+            false => "leaving it out",
         };
+        log::warn!(
+            "{} is already in {}, {}",
+            collision.path.display(),
+            dst.display(),
+            what
+        );
+        output.emit(Event::Collision(CollisionRecord::new(collision)));
+    }
+    for entry in merge.mapping.entries() {
+        log::info!("will map {} -> {}", entry.src, entry.dst);
+    }
 
-        let Some(Yaml::String(guid)) = hash.get(&guid_key) else {
-            log::error!(
-                "expecting guid field with string value in .meta: {}",
-                entry.path().display()
+    output.mapping(&merge.mapping);
+    let report = match merge.run(args.matching.options(), args.force, context.partial) {
+        Ok(report) => report,
+        Err(Error::Incomplete { errors, .. }) => {
+            errors.into_iter().for_each(|e| output.fail(e));
+            output.exit(
+                EXIT_FAILURE,
+                "some files could not be read or parsed, nothing was copied; \
+                 use --partial to copy the others anyway",
             );
+        }
+        Err(e) => {
+            output.fail(e);
+            output.exit(EXIT_FAILURE, "could not copy every file");
+        }
+    };
+    record(report, &merge.mapping, output);
+
+    if args.force {
+        log::info!("copied {} files into {}", merge.files.len(), dst.display());
+    } else {
+        log::info!(
+            "will copy {} files into {}",
+            merge.files.len(),
+            dst.display()
+        );
+        log::warn!("Dry-run: no changes made. Use --force or -f to apply changes.");
+    }
+}
+
+fn check_metas(context: &Context, args: CheckArgs, output: &mut Output) {
+    let CheckArgs {
+        dirs,
+        fix,
+        force,
+        guids,
+    } = args;
+    let project = &context.project;
+    let mut dirs = dirs
+        .iter()
+        .map(|dir| context.absolute(dir))
+        .collect::<Vec<_>>();
+    if dirs.is_empty() {
+        dirs.push(project.join("Assets"));
+    }
+    for dir in &dirs {
+        require_dir("folder", dir);
+    }
+
+    let guids = guids.source();
+    let mut found = 0;
+    let mut remaining = 0;
+    for dir in &dirs {
+        let (check, errors) = MetaCheck::run(dir);
+        errors.into_iter().for_each(|e| output.fail(e));
+
+        for meta in &check.orphans {
+            log::warn!("{} has no asset", meta.display());
+        }
+        for asset in &check.missing {
+            log::warn!("{} has no .meta", asset.display());
+        }
+        for e in &check.invalid {
+            log::warn!("{}", e);
+        }
+        found += check.orphans.len() + check.missing.len() + check.invalid.len();
+        remaining += check.invalid.len();
+        if !(fix && force) {
+            remaining += check.orphans.len() + check.missing.len();
+        }
+        if !fix {
             continue;
-        };
+        }
 
-        let guid = match uuid::Uuid::parse_str(guid) {
-            Ok(guid) => guid,
-            Err(e) => {
-                log::error!(
-                    "{} parsing uuid {} in .meta: {}",
-                    e,
-                    guid,
-                    entry.path().display()
+        for meta in &check.orphans {
+            if !force {
+                log::info!("will delete {}", meta.display());
+            } else if let Err(e) = check::remove_orphan(meta) {
+                output.fail(e);
+            } else {
+                log::info!("deleted {}", meta.display());
+            }
+        }
+        for asset in &check.missing {
+            let guid = guids.new_asset_guid(&relative_path(project, asset));
+            if !force {
+                log::info!(
+                    "will write a .meta with GUID {} for {}",
+                    guid.simple(),
+                    asset.display()
                 );
                 continue;
             }
-        };
+            match check::write_meta(asset, &guid) {
+                Ok(meta) => log::info!("wrote {}", meta.display()),
+                Err(e) => output.fail(e),
+            }
+        }
+    }
 
-        let new_guid = Uuid::new_v4();
-        log::info!("will map {} -> {}", guid, new_guid);
-        mapping.push((guid.simple().to_string(), new_guid.simple().to_string()));
+    if fix && !force {
+        log::warn!("Dry-run: no changes made. Use --force or -f to apply changes.");
     }
+    if remaining > 0 {
+        output.exit(
+            EXIT_MISMATCHED,
+            &format!("{} .meta problems remain", remaining),
+        );
+    }
+    if found == 0 {
+        log::info!("every asset has a valid .meta");
+    }
+}
 
-    mapping
+fn revert_journal(context: &Context, args: RevertArgs, output: &mut Output) {
+    let path = context.absolute(&args.journal);
+    let journal = match Journal::load(&path) {
+        Ok(journal) => journal,
+        Err(e) => {
+            output.fail(e);
+            output.exit(EXIT_FAILURE, "could not read the journal");
+        }
+    };
+
+    match journal.revert(&context.project) {
+        Ok(reverted) => log::info!("reverted {} files", reverted),
+        Err(errors) => {
+            let changed = errors.iter().all(|e| matches!(e, Error::Conflict { .. }));
+            errors.into_iter().for_each(|e| output.fail(e));
+            let code = if changed { EXIT_CHANGED } else { EXIT_FAILURE };
+            output.exit(code, "refusing to revert");
+        }
+    }
 }
 
-fn apply_mapping(dir: &Path, ignore: &[String], mapping: &[(String, String)], force: bool) {
-    for entry in WalkDir::new(dir) {
-        let entry = entry.unwrap();
+fn find_duplicates(context: &Context, args: DuplicatesArgs, output: &mut Output) {
+    let project = &context.project;
+    let scan = args.scan.resolve(&context.working_dir, project);
+    let duplicates = duplicates::find(&read(&scan, output));
+    for duplicate in &duplicates {
+        log_duplicate(duplicate);
+    }
 
-        if !entry.file_type().is_file() {
-            continue;
+    if !args.fix {
+        if !duplicates.is_empty() {
+            output.exit(
+                EXIT_DUPLICATES,
+                &format!(
+                    "{} GUIDs are shared by several .meta files",
+                    duplicates.len()
+                ),
+            );
         }
+        log::info!("no duplicate GUIDs");
+        return;
+    }
 
-        let file_name = entry.file_name().to_string_lossy();
-        if ignore.iter().any(|ext| file_name.ends_with(ext)) {
-            continue;
+    for duplicate in &duplicates {
+        log::info!(
+            "keeping {} for {}",
+            duplicate.guid.simple(),
+            duplicate.metas[0].display()
+        );
+    }
+
+    let mapping = duplicates::fix_mapping(project, &duplicates, &scan.remapping.guids);
+    for entry in mapping.entries() {
+        log::info!(
+            "will map {} -> {} in {}",
+            entry.src,
+            entry.dst,
+            entry.scope.as_deref().unwrap_or(project).display()
+        );
+    }
+
+    let rewriter = scan
+        .rewriter(&mapping)
+        .force(args.force)
+        .partial(context.partial);
+    let journal = args
+        .journal
+        .filter(|_| args.force)
+        .map(|path| context.absolute(&path));
+    rewrite(rewriter, project, journal, &mapping, output);
+
+    if !args.force {
+        log::warn!("Dry-run: no changes made. Use --force or -f to apply changes.");
+    }
+}
+
+fn find_dangling(context: &Context, args: DanglingArgs, output: &mut Output) {
+    let project = &context.project;
+    let scan = args.scan.resolve(&context.working_dir, project);
+    let (defined, errors) = dangling::defined_guids(project, &scan.dirs, scan.files.walk_mode());
+    errors.into_iter().for_each(|e| output.fail(e));
+    if !project.join("Library/PackageCache").is_dir() {
+        log::warn!(
+            "{} has no Library/PackageCache, so references to downloaded \
+             packages are reported too; open the project in Unity first",
+            project.display()
+        );
+    }
+
+    let (references, errors) =
+        dangling::find(project, &scan.files, &defined, &scan.remapping.protected);
+    errors.into_iter().for_each(|e| output.fail(e));
+    for reference in &references {
+        log::warn!(
+            "{}:{}{} references {}, which no .meta file defines",
+            reference.path.display(),
+            reference.line,
+            reference
+                .file_id
+                .as_ref()
+                .map(|id| format!(" in object &{}", id))
+                .unwrap_or_default(),
+            reference.guid
+        );
+        output.emit(Event::Unresolved(UnresolvedRecord::dangling(
+            project, reference,
+        )));
+    }
+
+    if !references.is_empty() {
+        output.exit(
+            EXIT_DANGLING,
+            &format!("{} references resolve to no asset", references.len()),
+        );
+    }
+    log::info!("every reference resolves to an asset");
+}
+
+/// Exits unless `dir` is a directory, calling it `what` in the error.
+fn require_dir(what: &str, dir: &Path) {
+    if !dir.is_dir() {
+        log::error!("{} {} is not a directory", what, dir.display());
+        std::process::exit(EXIT_FAILURE);
+    }
+}
+
+/// The value of `result`, or exits logging its error.
+fn or_exit<T>(result: Result<T, impl std::fmt::Display>) -> T {
+    result.unwrap_or_else(|e| {
+        log::error!("{}", e);
+        std::process::exit(EXIT_FAILURE);
+    })
+}
+
+/// What a run reports besides its log: failures, summarized when it ends, and
+/// with `--report` a JSON description of everything it did on stdout.
+struct Output {
+    root: PathBuf,
+    format: Option<ReportFormat>,
+    report: Report,
+    failures: Vec<Error>,
+}
+
+impl Output {
+    fn new(root: &Path, format: Option<ReportFormat>) -> Self {
+        Self {
+            root: root.to_owned(),
+            format,
+            report: Report::default(),
+            failures: Vec::new(),
         }
+    }
 
-        let mut contents = match std::fs::read_to_string(entry.path()) {
-            Ok(contents) => contents,
-            Err(e) => {
-                log::error!("reading {}: {}", entry.path().display(), e);
-                continue;
+    fn emit(&mut self, event: Event) {
+        if self.format == Some(ReportFormat::Jsonl) {
+            println!("{}", serde_json::to_string(&event).unwrap());
+        }
+        self.report.add(&event);
+    }
+
+    fn mapping(&mut self, mapping: &GuidMapping) {
+        for entry in mapping.entries() {
+            self.emit(Event::Mapping(MappingRecord::new(&self.root, entry)));
+        }
+    }
+
+    fn files(&mut self, files: &[FileReport], mapping: &GuidMapping) {
+        for file in files {
+            self.emit(Event::File(FileRecord::new(&self.root, file, mapping)));
+        }
+    }
+
+    fn fail(&mut self, e: Error) {
+        self.emit(Event::Error(ErrorRecord::new(&self.root, &e)));
+        self.failures.push(e);
+    }
+
+    /// Writes the end of the report: the whole document, or the summary line.
+    fn close(&self) {
+        match self.format {
+            Some(ReportFormat::Json) => {
+                println!("{}", serde_json::to_string_pretty(&self.report).unwrap());
             }
-        };
+            Some(ReportFormat::Jsonl) => {
+                let summary = Event::Summary(self.report.summary.clone());
+                println!("{}", serde_json::to_string(&summary).unwrap());
+            }
+            None => {}
+        }
+    }
 
-        let mut indices = Vec::new();
-        for (src, dst) in mapping {
-            indices.clear();
-            indices.extend(contents.match_indices(src).map(|(n, _)| n));
-            if indices.is_empty() {
-                continue;
+    /// Summarizes the failures, then exits with `code` and `message`.
+    fn exit(&self, code: i32, message: &str) -> ! {
+        if !self.failures.is_empty() {
+            log::error!("{} failures:", self.failures.len());
+            for e in &self.failures {
+                log::error!("    {}", e);
             }
+        }
+
+        log::error!("{}", message);
+        self.close();
+        std::process::exit(code);
+    }
+
+    /// Ends a run that got to the end, which is incomplete if anything failed.
+    fn finish(&self) {
+        if !self.failures.is_empty() {
+            self.exit(EXIT_INCOMPLETE, "some files could not be processed");
+        }
+        self.close();
+    }
+}
+
+/// Runs `rewriter` over `root` and logs what it found, exiting if it could
+/// not finish. `journal` is only written by forced runs.
+fn rewrite(
+    mut rewriter: Rewriter,
+    root: &Path,
+    journal: Option<PathBuf>,
+    mapping: &GuidMapping,
+    output: &mut Output,
+) -> RewriteReport {
+    if let Some(path) = &journal {
+        rewriter = rewriter.journal(path);
+    }
+    output.mapping(mapping);
+
+    let report = match rewriter.run(root) {
+        Ok(report) => report,
+        Err(Error::Incomplete { errors, .. }) => {
+            errors.into_iter().for_each(|e| output.fail(e));
+            output.exit(
+                EXIT_FAILURE,
+                "some files could not be searched, nothing was written; \
+                 use --partial to rewrite the others anyway",
+            );
+        }
+        Err(e) => {
+            output.fail(e);
+            output.exit(EXIT_FAILURE, "could not rewrite every file");
+        }
+    };
+
+    if let Some(path) = journal {
+        log::info!("wrote journal to {}", path.display());
+    }
 
+    record(report, mapping, output)
+}
+
+/// Logs what a rewrite found and adds it to the output.
+fn record(mut report: RewriteReport, mapping: &GuidMapping, output: &mut Output) -> RewriteReport {
+    for file in &report.files {
+        for (i, offsets) in &file.hits {
             log::info!(
                 "will rewrite {} instances of {} -> {} in {}",
-                indices.len(),
-                src,
-                dst,
-                entry.path().display()
+                offsets.len(),
+                mapping[*i].src,
+                mapping[*i].dst,
+                file.path.display()
+            );
+        }
+
+        for (i, offsets) in &file.skipped {
+            log::warn!(
+                "skipped {} {} instances of {} in {} at offsets {:?}",
+                offsets.len(),
+                if file.binary { "binary" } else { "ambiguous" },
+                mapping[*i].src,
+                file.path.display(),
+                offsets
+            );
+        }
+    }
+
+    output.files(&report.files, mapping);
+    for e in std::mem::take(&mut report.errors) {
+        output.fail(e);
+    }
+    report
+}
+
+impl ScanArgs {
+    fn resolve(self, working_dir: &Path, project: &Path) -> Scan {
+        let mut dirs = self
+            .scan_dirs
+            .iter()
+            .chain(&self.scan_dir)
+            .map(|dir| absolute(working_dir, dir))
+            .collect::<Vec<_>>();
+        if dirs.is_empty() {
+            dirs.push(project.to_owned());
+        }
+
+        for dir in &dirs {
+            let inside = dir.starts_with(project);
+            let alongside = dir.parent().is_some() && dir.parent() == project.parent();
+            require_dir("scan directory", dir);
+            if !(inside || alongside) {
+                log::error!(
+                    "scan directory {} must be inside or next to the project",
+                    dir.display()
+                );
+                std::process::exit(EXIT_FAILURE);
+            }
+            log::info!("scanning: {}", dir.display());
+        }
+        let mut exclude = self.exclude;
+        if let Some(ignore) = self.ignore {
+            log::warn!("--ignore is deprecated, use --exclude '*.EXT' instead");
+            exclude.extend(ignore.split(',').map(|ext| format!("*.{}", ext.trim())));
+        }
+
+        let files = if self.no_default_exclude {
+            FileFilter::new(self.include, exclude)
+        } else {
+            FileFilter::with_default_exclude(self.include, exclude)
+        };
+        let files = or_exit(files).walk(self.walk);
+
+        Scan {
+            project: project.to_owned(),
+            dirs,
+            strict: self.strict,
+            files,
+            matching: self.matching.options(),
+            remapping: self.mapping.resolve(working_dir),
+        }
+    }
+}
+
+impl MatchArgs {
+    fn options(&self) -> MatchOptions {
+        MatchOptions {
+            guid_context: self.guid_context,
+            mode: self.mode,
+        }
+    }
+}
+
+impl MappingArgs {
+    fn resolve(self, working_dir: &Path) -> Remapping {
+        let filter = or_exit(MetaFilter::new(self.only, self.except));
+
+        let guids = self.guids.source();
+
+        let against = self.against.map(|root| {
+            let root = absolute(working_dir, &root);
+            require_dir("target project", &root);
+            let (target, errors) = Target::read(&root);
+            for e in errors {
+                log::warn!("target project: {}", e);
+            }
+            log::info!(
+                "target project {}: {} .meta files",
+                root.display(),
+                target.meta_count()
             );
+            target
+        });
 
-            if force {
-                for n in &indices {
-                    let n = *n;
-                    unsafe {
-                        contents[n..(n + UUID_STR_LEN)]
-                            .as_bytes_mut()
-                            .copy_from_slice(dst.as_bytes())
-                    }
-                }
+        Remapping {
+            filter,
+            guids,
+            protected: self.protect.resolve(working_dir),
+            against,
+        }
+    }
+}
+
+impl GuidArgs {
+    fn source(self) -> GuidSource {
+        match (self.seed, self.namespace) {
+            (Some(seed), _) => GuidSource::from_seed(&seed),
+            (None, Some(namespace)) => GuidSource::Namespace(namespace),
+            (None, None) => GuidSource::Random,
+        }
+    }
+}
+
+impl ProtectArgs {
+    fn resolve(&self, working_dir: &Path) -> ProtectedGuids {
+        let mut guids = self.protect.clone();
+        if let Some(path) = &self.protect_file {
+            guids.extend(or_exit(ProtectedGuids::read(&absolute(working_dir, path))));
+        }
+        ProtectedGuids::new(guids)
+    }
+}
+
+impl Scan {
+    /// What the paths of the .meta files found are relative to: the project
+    /// for scan directories inside it, or else the scan directory itself.
+    fn roots(&self, project: &Path) -> Vec<PathBuf> {
+        self.dirs
+            .iter()
+            .map(|dir| match dir.starts_with(project) {
+                true => project.to_owned(),
+                false => dir.clone(),
+            })
+            .collect()
+    }
+
+    fn rewriter<'a>(&self, mapping: &'a GuidMapping) -> Rewriter<'a> {
+        Rewriter::new(mapping)
+            .files(self.files.clone())
+            .matching(self.matching)
+    }
+}
+
+/// Globs match like in .gitignore: `*` stays within one path component.
+fn parse_glob(glob: &str) -> Result<Glob, globset::Error> {
+    GlobBuilder::new(glob).literal_separator(true).build()
+}
+
+/// `path` made absolute against `base`, with `.` and `..` resolved lexically.
+fn absolute(base: &Path, path: &Path) -> PathBuf {
+    let mut absolute = PathBuf::new();
+    for component in base.join(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                absolute.pop();
             }
+            component => absolute.push(component),
+        }
+    }
+    absolute
+}
+
+/// The .meta files to remap, without those claiming a protected GUID. With
+/// `--strict`, exits before anything is written if any of them could not be
+/// read.
+fn read(scan: &Scan, output: &mut Output) -> Vec<Meta> {
+    let remapping = &scan.remapping;
+    let (mut metas, mut errors) = read_metas(&scan.dirs, &remapping.filter, scan.files.walk_mode());
+    // The GUID of a .meta the rewrite leaves alone, e.g. under Library, would
+    // be remapped everywhere but in the .meta itself.
+    let rewritten =
+        |path: &Path| !path.starts_with(&scan.project) || scan.files.is_match(&scan.project, path);
+    metas.retain(|meta| rewritten(&meta.path));
+    errors.retain(|e| rewritten(e.path()));
+    retain_unprotected(&mut metas, &remapping.protected);
+
+    if scan.strict && !errors.is_empty() {
+        errors.into_iter().for_each(|e| output.fail(e));
+        output.exit(
+            EXIT_STRICT,
+            "some .meta files could not be read, nothing was written",
+        );
+    }
+
+    errors.into_iter().for_each(|e| output.fail(e));
+    metas
+}
+
+fn retain_unprotected(metas: &mut Vec<Meta>, protected: &ProtectedGuids) {
+    metas.retain(|meta| {
+        let protected = protected.contains(&meta.guid);
+        if protected {
+            log::warn!(
+                "{} claims protected GUID {}, leaving it alone",
+                meta.path.display(),
+                meta.guid.simple()
+            );
+        }
+        !protected
+    });
+}
+
+/// Warns about assembly definitions referencing GUIDs that neither `mapping`
+/// nor any .meta file in the project defines.
+fn check_references(scan: &Scan, project: &Path, mapping: &GuidMapping, output: &mut Output) {
+    let mapped = mapping
+        .entries()
+        .iter()
+        .flat_map(|entry| [entry.src.as_str(), entry.dst.as_str()])
+        .collect::<HashSet<_>>();
+    let (references, errors) = asmdef::find_references(project, &scan.files);
+    for e in errors {
+        // The rewrite walked and parsed the same files, and may have failed
+        // on them already.
+        if !output
+            .failures
+            .iter()
+            .any(|failure| failure.path() == e.path())
+        {
+            output.fail(e);
+        }
+    }
+    let references = references
+        .into_iter()
+        .filter(|reference| !mapped.contains(reference.guid.as_str()))
+        .collect::<Vec<_>>();
+    if references.is_empty() {
+        return;
+    }
+
+    // Every .meta counts, including those of packages under Library.
+    let (metas, _) = read_metas(&[project.to_owned()], &MetaFilter::default(), WalkMode::All);
+    let defined = metas
+        .iter()
+        .map(|meta| meta.guid.simple().to_string())
+        .collect::<HashSet<_>>();
+
+    for reference in references {
+        if defined.contains(&reference.guid) {
+            continue;
         }
+        log::warn!(
+            "{} references assembly {}, which no .meta file defines",
+            reference.path.display(),
+            reference.guid
+        );
+        output.emit(Event::Unresolved(UnresolvedRecord::new(
+            project, &reference,
+        )));
+    }
+}
+
+fn log_duplicate(duplicate: &duplicates::Duplicate) {
+    log::warn!(
+        "{} is shared by {} .meta files:",
+        duplicate.guid.simple(),
+        duplicate.metas.len()
+    );
+    for meta in &duplicate.metas {
+        log::warn!("    {}", meta.display());
+    }
+}
 
-        if force {
-            if let Err(e) = std::fs::write(entry.path(), contents) {
-                log::error!("writing {}: {}", entry.path().display(), e);
-            };
+/// Pairs the GUIDs of `metas` with new ones. `roots` are what their paths are
+/// relative to, see [`Target::collides`].
+fn make_mapping(metas: &[Meta], remapping: &Remapping, roots: &[PathBuf]) -> GuidMapping {
+    let duplicates = duplicates::find(metas);
+    for duplicate in &duplicates {
+        log_duplicate(duplicate);
+    }
+    if !duplicates.is_empty() {
+        log::warn!(
+            "{} GUIDs are shared by several .meta files and stay shared; \
+             use the duplicates command to split them",
+            duplicates.len()
+        );
+    }
+
+    let colliding;
+    let metas = match &remapping.against {
+        Some(target) => {
+            colliding = target.colliding(metas, roots);
+            log::info!(
+                "{} of {} .meta files collide with {}",
+                colliding.len(),
+                metas.len(),
+                target.root().display()
+            );
+            &colliding
         }
+        None => metas,
+    };
+
+    let mapping = GuidMapping::generate(metas, &remapping.guids, &remapping.protected);
+    for entry in mapping.entries() {
+        log::info!("will map {} -> {}", entry.src, entry.dst);
     }
+
+    mapping
 }
@@ -0,0 +1,144 @@
+use std::{collections::HashSet, path::PathBuf};
+
+use uuid::Uuid;
+
+use crate::{Meta, ProtectedGuids};
+
+/// Where the replacement for each remapped GUID comes from.
+#[derive(Clone, Debug)]
+pub enum GuidSource {
+    Random,
+    /// Each new GUID is the UUIDv5 of this namespace and the old GUID, so the
+    /// same input always gets the same GUIDs.
+    Namespace(Uuid),
+}
+
+impl GuidSource {
+    /// A deterministic source whose namespace is derived from `seed`.
+    pub fn from_seed(seed: &str) -> Self {
+        GuidSource::Namespace(Uuid::new_v5(&Uuid::NAMESPACE_OID, seed.as_bytes()))
+    }
+
+    pub fn new_guid(&self, old: &Uuid) -> Uuid {
+        self.derive(old.simple().to_string())
+    }
+
+    /// A new GUID for the copy of `old` at `copy`, distinct for every copy.
+    pub fn new_copy_guid(&self, old: &Uuid, copy: &str) -> Uuid {
+        self.derive(format!("{}:{}", old.simple(), copy))
+    }
+
+    /// A GUID for the new asset at `path`, e.g. one without a .meta.
+    pub fn new_asset_guid(&self, path: &str) -> Uuid {
+        self.derive(path.to_owned())
+    }
+
+    fn derive(&self, name: String) -> Uuid {
+        match self {
+            GuidSource::Random => Uuid::new_v4(),
+            GuidSource::Namespace(namespace) => Uuid::new_v5(namespace, name.as_bytes()),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MappingEntry {
+    /// The old GUID, as 32 lowercase hex digits.
+    pub src: String,
+    /// The new GUID, as 32 lowercase hex digits.
+    pub dst: String,
+    /// The .meta file the old GUID was read from, if any.
+    pub meta: Option<PathBuf>,
+    /// Only rewrite files inside this path, if set.
+    pub scope: Option<PathBuf>,
+}
+
+impl MappingEntry {
+    pub fn new(src: &Uuid, dst: &Uuid) -> Self {
+        Self {
+            src: src.simple().to_string(),
+            dst: dst.simple().to_string(),
+            meta: None,
+            scope: None,
+        }
+    }
+}
+
+/// Old GUIDs and what to replace them with.
+#[derive(Clone, Debug, Default)]
+pub struct GuidMapping {
+    entries: Vec<MappingEntry>,
+}
+
+impl GuidMapping {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pairs every distinct GUID in `metas` with a new one from `guids`,
+    /// except `protected` ones. GUIDs shared by several metas are mapped once,
+    /// so the copies stay shared.
+    pub fn generate(metas: &[Meta], guids: &GuidSource, protected: &ProtectedGuids) -> Self {
+        let mut seen = HashSet::new();
+        let mut mapping = Self::new();
+
+        for meta in metas {
+            if protected.contains(&meta.guid) || !seen.insert(meta.guid) {
+                continue;
+            }
+
+            mapping.push(MappingEntry {
+                meta: Some(meta.path.clone()),
+                ..MappingEntry::new(&meta.guid, &guids.new_guid(&meta.guid))
+            });
+        }
+
+        mapping
+    }
+
+    /// A mapping from `(old, new)` GUID pairs, such as the ones in a plan.
+    pub fn from_pairs(pairs: impl IntoIterator<Item = (String, String)>) -> Self {
+        let entries = pairs
+            .into_iter()
+            .map(|(src, dst)| MappingEntry {
+                src,
+                dst,
+                meta: None,
+                scope: None,
+            })
+            .collect();
+
+        Self { entries }
+    }
+
+    pub fn pairs(&self) -> Vec<(String, String)> {
+        self.entries
+            .iter()
+            .map(|entry| (entry.src.clone(), entry.dst.clone()))
+            .collect()
+    }
+
+    pub fn push(&mut self, entry: MappingEntry) {
+        self.entries.push(entry);
+    }
+
+    pub fn entries(&self) -> &[MappingEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl std::ops::Index<usize> for GuidMapping {
+    type Output = MappingEntry;
+
+    fn index(&self, index: usize) -> &MappingEntry {
+        &self.entries[index]
+    }
+}
@@ -0,0 +1,153 @@
+//! Copying the assets of one project into another. An asset whose path the
+//! destination already uses is left out, and references to it are pointed at
+//! the destination's asset instead. An asset whose GUID the destination
+//! already gives to another asset gets a new one. Only the copies are
+//! rewritten.
+
+use std::{
+    collections::{BTreeSet, HashSet},
+    path::{Path, PathBuf},
+};
+
+use uuid::Uuid;
+
+use crate::{
+    meta_path, read_meta_guid, read_metas, walk::walk_files, Error, FileFilter, GuidMapping,
+    GuidSource, MappingEntry, MatchOptions, Meta, MetaFilter, ProtectedGuids, RewriteReport,
+    Rewriter, WalkMode,
+};
+
+/// What merging one project into another does.
+pub struct Merge {
+    pub src: PathBuf,
+    pub dst: PathBuf,
+    /// The files to copy, relative to both projects.
+    pub files: Vec<PathBuf>,
+    /// Assets left out because the destination has one at the same path.
+    pub collisions: Vec<PathCollision>,
+    /// New GUIDs for the copied assets whose GUID the destination already
+    /// uses, and the destination's GUIDs for the assets left out.
+    pub mapping: GuidMapping,
+}
+
+pub struct PathCollision {
+    /// The asset's path, relative to both projects.
+    pub path: PathBuf,
+    pub src_guid: Option<Uuid>,
+    pub dst_guid: Option<Uuid>,
+}
+
+impl Merge {
+    /// Plans copying the files of `src` kept by `filter` into `dst`, with new
+    /// GUIDs from `guids` for colliding ones that aren't `protected`.
+    pub fn new(
+        src: &Path,
+        dst: &Path,
+        filter: &FileFilter,
+        guids: &GuidSource,
+        protected: &ProtectedGuids,
+    ) -> (Self, Vec<Error>) {
+        let (paths, mut errors) = walk_files(src, filter);
+
+        let mut files = Vec::new();
+        let mut collided = BTreeSet::new();
+        for path in paths {
+            // Walked paths are all under `src`.
+            let path = path.strip_prefix(src).unwrap().to_owned();
+            let asset = asset_path(&path);
+            if dst.join(&asset).exists() || dst.join(meta_path(&asset)).exists() {
+                collided.insert(asset);
+            } else {
+                files.push(path);
+            }
+        }
+
+        let mut read_guid = |path: PathBuf| match read_meta_guid(&path) {
+            Ok(guid) => Some(guid),
+            Err(e) => {
+                if path.exists() {
+                    errors.push(e);
+                }
+                None
+            }
+        };
+
+        let mut mapping = GuidMapping::new();
+        let mut collisions = Vec::new();
+        for asset in collided {
+            let src_guid = read_guid(src.join(meta_path(&asset)));
+            let dst_guid = read_guid(dst.join(meta_path(&asset)));
+            if let (Some(from), Some(to)) = (src_guid, dst_guid) {
+                if from != to && !protected.contains(&from) && !protected.contains(&to) {
+                    mapping.push(MappingEntry {
+                        meta: Some(src.join(meta_path(&asset))),
+                        ..MappingEntry::new(&from, &to)
+                    });
+                }
+            }
+            collisions.push(PathCollision {
+                path: asset,
+                src_guid,
+                dst_guid,
+            });
+        }
+
+        let (dst_metas, mut dst_errors) =
+            read_metas(&[dst.to_owned()], &MetaFilter::default(), WalkMode::All);
+        errors.append(&mut dst_errors);
+        let used = dst_metas
+            .into_iter()
+            .map(|meta| meta.guid)
+            .collect::<HashSet<_>>();
+
+        let mut colliding = Vec::new();
+        for path in &files {
+            if !path.to_string_lossy().ends_with(".meta") {
+                continue;
+            }
+            let path = src.join(path);
+            match read_meta_guid(&path) {
+                Ok(guid) if used.contains(&guid) => colliding.push(Meta { path, guid }),
+                Ok(_) => {}
+                Err(e) => errors.push(e),
+            }
+        }
+        for entry in GuidMapping::generate(&colliding, guids, protected).entries() {
+            mapping.push(entry.clone());
+        }
+
+        let merge = Self {
+            src: src.to_owned(),
+            dst: dst.to_owned(),
+            files,
+            collisions,
+            mapping,
+        };
+        (merge, errors)
+    }
+
+    /// Copies the files, rewriting them with the mapping. Without `force`,
+    /// only reports what would be rewritten. Unless `partial` is set, nothing
+    /// is copied if any file could not be read.
+    pub fn run(
+        &self,
+        matching: MatchOptions,
+        force: bool,
+        partial: bool,
+    ) -> Result<RewriteReport, Error> {
+        Rewriter::new(&self.mapping)
+            .matching(matching)
+            .force(force)
+            .partial(partial)
+            .copy(&self.src, &self.dst, &self.files)
+    }
+}
+
+/// The asset a file belongs to: itself, or the asset of a .meta.
+fn asset_path(path: &Path) -> PathBuf {
+    let name = path.as_os_str().to_string_lossy();
+    match name.strip_suffix(".meta") {
+        Some(asset) => PathBuf::from(asset),
+        None => path.to_owned(),
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use rayon::prelude::*;
+use uuid::Uuid;
+use yaml_rust::{Yaml, YamlLoader};
+
+use crate::{is_simple_guid, relative_path, walk::walk_files, Error, FileFilter, WalkMode};
+
+/// A .meta file and the GUID it gives its asset.
+#[derive(Clone, Debug)]
+pub struct Meta {
+    pub path: PathBuf,
+    pub guid: Uuid,
+}
+
+/// Which .meta files produce mapping entries, by globs matched against their
+/// path relative to the directory they were found in.
+#[derive(Clone, Debug, Default)]
+pub struct MetaFilter {
+    only: Option<GlobSet>,
+    except: GlobSet,
+}
+
+impl MetaFilter {
+    /// Keeps the .meta files matching any of `only`, or all of them if it is
+    /// empty, minus the ones matching any of `except`. A glob matches a .meta
+    /// if it matches either its path or the path of its asset, so both
+    /// `Assets/Vendor/**` and `**/*.shader.meta` work.
+    pub fn new(
+        only: impl IntoIterator<Item = Glob>,
+        except: impl IntoIterator<Item = Glob>,
+    ) -> Result<Self, globset::Error> {
+        let build = |globs: &mut dyn Iterator<Item = Glob>| {
+            let mut builder = GlobSetBuilder::new();
+            for glob in globs {
+                builder.add(glob);
+            }
+            builder.build()
+        };
+
+        let only = build(&mut only.into_iter())?;
+        Ok(Self {
+            only: (!only.is_empty()).then_some(only),
+            except: build(&mut except.into_iter())?,
+        })
+    }
+
+    /// Whether the .meta at `path`, found under `dir`, is kept.
+    pub fn is_match(&self, dir: &Path, path: &Path) -> bool {
+        let meta = relative_path(dir, path);
+        let asset = meta.strip_suffix(".meta").unwrap_or(&meta);
+        let matches = |set: &GlobSet| set.is_match(&meta) || set.is_match(asset);
+
+        self.only.as_ref().is_none_or(matches) && !matches(&self.except)
+    }
+}
+
+/// Every .meta file under `dirs` visited in `mode` and kept by `filter`, in
+/// path order, and the ones that could not be read.
+pub fn read_metas(
+    dirs: &[PathBuf],
+    filter: &MetaFilter,
+    mode: WalkMode,
+) -> (Vec<Meta>, Vec<Error>) {
+    let mut paths = BTreeSet::new();
+    let mut errors = Vec::new();
+    for dir in dirs {
+        let (files, mut walk_errors) = walk_files(dir, &FileFilter::default().walk(mode));
+        paths.extend(files.into_iter().filter(|path| {
+            path.to_string_lossy().ends_with(".meta") && filter.is_match(dir, path)
+        }));
+        errors.append(&mut walk_errors);
+    }
+
+    let results = paths
+        .into_par_iter()
+        .map(|path| {
+            let guid = read_meta_guid(&path)?;
+            Ok(Meta { path, guid })
+        })
+        .collect::<Vec<_>>();
+
+    let mut metas = Vec::new();
+    for result in results {
+        match result {
+            Ok(meta) => metas.push(meta),
+            Err(e) => errors.push(e),
+        }
+    }
+
+    (metas, errors)
+}
+
+pub fn read_meta_guid(path: &Path) -> Result<Uuid, Error> {
+    let yaml = std::fs::read_to_string(path).map_err(|source| Error::Read {
+        path: path.to_owned(),
+        source,
+    })?;
+
+    parse_meta_guid(path, &yaml)
+}
+
+/// The GUID in `yaml`, the contents of the .meta file at `path`.
+pub fn parse_meta_guid(path: &Path, yaml: &str) -> Result<Uuid, Error> {
+    let malformed = |reason: String| Error::MalformedMeta {
+        path: path.to_owned(),
+        reason,
+    };
+
+    let yaml = match YamlLoader::load_from_str(yaml) {
+        Ok(mut xs) if xs.len() == 1 => xs.pop().unwrap(),
+        Ok(xs) => return Err(malformed(format!("unexpected {} documents", xs.len()))),
+        Err(e) => return Err(malformed(e.to_string())),
+    };
+
+    let Yaml::Hash(hash) = yaml else {
+        return Err(malformed("unexpected non-hash".to_owned()));
+    };
+
+    // A GUID with no letters in it loads as a number, but the loader keeps
+    // the text of numbers too large for an integer.
+    let (Some(Yaml::String(guid)) | Some(Yaml::Real(guid))) =
+        hash.get(&Yaml::String("guid".to_owned()))
+    else {
+        return Err(malformed(
+            "expecting guid field with string value".to_owned(),
+        ));
+    };
+
+    // Unity only writes 32 hex digits, never the hyphenated forms `Uuid`
+    // also accepts, which no reference would match.
+    is_simple_guid(guid)
+        .then(|| Uuid::parse_str(guid).ok())
+        .flatten()
+        .ok_or_else(|| Error::InvalidGuid {
+            path: path.to_owned(),
+            guid: guid.clone(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(guid: &str) -> Result<Uuid, Error> {
+        let yaml = format!("fileFormatVersion: 2\nguid: {}\n", guid);
+        parse_meta_guid(Path::new("a.meta"), &yaml)
+    }
+
+    #[test]
+    fn guids_are_32_hex_digits() {
+        assert!(parse("0123456789abcdef0123456789abcdef").is_ok());
+        assert!(parse("01234567890123456789012345678901").is_ok());
+
+        for guid in [
+            "01234567-89ab-cdef-0123-456789abcdef",
+            "urn:uuid:01234567-89ab-cdef-0123-456789abcdef",
+            "0123456789abcdef0123456789abcde",
+            "0123456789abcdef0123456789abcdeg",
+        ] {
+            assert!(
+                matches!(parse(guid), Err(Error::InvalidGuid { .. })),
+                "{}",
+                guid
+            );
+        }
+    }
+}
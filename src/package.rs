@@ -0,0 +1,183 @@
+//! Unity packages: gzipped tar archives with one `<guid>/` folder per asset,
+//! holding the asset itself in `asset`, its .meta in `asset.meta` and its path
+//! in the project in `pathname`. Packages are read and written whole in
+//! memory, never extracted.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+
+use crate::{
+    meta_path, parse_meta_guid, staging, Error, GuidMapping, MatchOptions, Meta, RewriteReport,
+    Rewriter,
+};
+
+pub struct Package {
+    entries: Vec<Entry>,
+}
+
+struct Entry {
+    header: tar::Header,
+    /// The path in the archive, e.g. `<guid>/asset`.
+    path: String,
+    data: Vec<u8>,
+}
+
+impl Entry {
+    /// The folder the entry is in, which is named after its asset's GUID, and
+    /// the rest of its path.
+    fn split(&self) -> Option<(&str, &str)> {
+        let path = self.path.strip_prefix("./").unwrap_or(&self.path);
+        Some(path.split_once('/').unwrap_or((path, ""))).filter(|(folder, _)| !folder.is_empty())
+    }
+}
+
+impl Package {
+    pub fn read(path: &Path) -> Result<Self, Error> {
+        let read = |source| Error::Read {
+            path: path.to_owned(),
+            source,
+        };
+
+        let file = File::open(path).map_err(read)?;
+        let mut archive = tar::Archive::new(GzDecoder::new(BufReader::new(file)));
+        let mut entries = Vec::new();
+        for entry in archive.entries().map_err(read)? {
+            let mut entry = entry.map_err(read)?;
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).map_err(read)?;
+            entries.push(Entry {
+                header: entry.header().clone(),
+                path: String::from_utf8_lossy(&entry.path_bytes()).into_owned(),
+                data,
+            });
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// Writes the package to `path`, replacing it atomically if it exists.
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        let write = |source| Error::Write {
+            path: path.to_owned(),
+            source,
+        };
+
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for entry in &self.entries {
+            let mut header = entry.header.clone();
+            header.set_size(entry.data.len() as u64);
+            builder
+                .append_data(&mut header, &entry.path, entry.data.as_slice())
+                .map_err(write)?;
+        }
+        let contents = builder
+            .into_inner()
+            .and_then(|encoder| encoder.finish())
+            .map_err(write)?;
+
+        let temp = staging::stage(path, &contents).map_err(write)?;
+        staging::commit_all([(temp.as_path(), path)]).map(drop)
+    }
+
+    /// The path in the project of the asset in each folder of the package.
+    fn pathnames(&self) -> HashMap<String, PathBuf> {
+        self.entries
+            .iter()
+            .filter_map(|entry| match entry.split()? {
+                (folder, "pathname") => {
+                    let pathname = String::from_utf8_lossy(&entry.data);
+                    let pathname = pathname.lines().next()?.trim();
+                    Some((folder.to_owned(), PathBuf::from(pathname)))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The .meta of every asset in the package, with the path it would have
+    /// in the project, and those that could not be read.
+    pub fn metas(&self) -> (Vec<Meta>, Vec<Error>) {
+        let pathnames = self.pathnames();
+        let mut metas = Vec::new();
+        let mut errors = Vec::new();
+
+        for entry in &self.entries {
+            let Some((folder, "asset.meta")) = entry.split() else {
+                continue;
+            };
+            let path = match pathnames.get(folder) {
+                Some(pathname) => meta_path(pathname),
+                None => PathBuf::from(&entry.path),
+            };
+
+            let yaml = String::from_utf8_lossy(&entry.data);
+            match parse_meta_guid(&path, &yaml) {
+                Ok(guid) if guid.simple().to_string() != folder => errors.push(Error::Parse {
+                    path,
+                    reason: format!("GUID {} is not its folder's, {}", guid.simple(), folder),
+                }),
+                Ok(guid) => metas.push(Meta { path, guid }),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        (metas, errors)
+    }
+
+    /// Applies `mapping` to every asset and .meta in the package, reporting
+    /// them by their path in the project. With `force`, they are rewritten
+    /// and the folders of remapped assets renamed after their new GUIDs.
+    pub fn rewrite(
+        &mut self,
+        mapping: &GuidMapping,
+        matching: MatchOptions,
+        force: bool,
+    ) -> RewriteReport {
+        let pathnames = self.pathnames();
+        let mut files = Vec::new();
+        let mut indices = Vec::new();
+        for (i, entry) in self.entries.iter_mut().enumerate() {
+            let path = match entry.split() {
+                Some((folder, "asset")) => pathnames.get(folder).cloned(),
+                Some((folder, "asset.meta")) => pathnames.get(folder).map(|p| meta_path(p)),
+                _ => None,
+            };
+            if let Some(path) = path {
+                files.push((path, std::mem::take(&mut entry.data)));
+                indices.push(i);
+            }
+        }
+
+        let report = Rewriter::new(mapping)
+            .matching(matching)
+            .force(force)
+            .run_in_memory(&mut files);
+        for (i, (_, data)) in indices.into_iter().zip(files) {
+            self.entries[i].data = data;
+        }
+
+        if force {
+            let renames = mapping
+                .entries()
+                .iter()
+                .map(|entry| (entry.src.as_str(), entry.dst.as_str()))
+                .collect::<HashMap<_, _>>();
+            for entry in &mut self.entries {
+                let Some((folder, _)) = entry.split() else {
+                    continue;
+                };
+                if let Some(dst) = renames.get(folder) {
+                    entry.path = entry.path.replacen(folder, dst, 1);
+                }
+            }
+        }
+
+        report
+    }
+}
@@ -0,0 +1,269 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    is_simple_guid, relative_path, Error, FileFilter, FileReport, GuidMapping, MatchOptions,
+    ProtectedGuids, WalkMode,
+};
+
+/// A reviewable record of a rewrite: the mapping to apply and every file it
+/// touches, as seen when the plan was made.
+#[derive(Serialize, Deserialize)]
+pub struct RewritePlan {
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// File name suffixes left alone, from plans made before `exclude`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignore: Vec<String>,
+    #[serde(default)]
+    pub walk: WalkMode,
+    #[serde(flatten)]
+    pub matching: MatchOptions,
+    pub mapping: Vec<(String, String)>,
+    pub files: Vec<PlannedFile>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq)]
+pub struct PlannedFile {
+    pub path: String,
+    pub sha256: String,
+    pub hits: BTreeMap<String, usize>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub skipped: BTreeMap<String, usize>,
+}
+
+impl RewritePlan {
+    pub fn new(
+        root: &Path,
+        filter: &FileFilter,
+        matching: MatchOptions,
+        mapping: &GuidMapping,
+        files: &[FileReport],
+    ) -> Self {
+        let mapping = mapping.pairs();
+        let files = files
+            .iter()
+            .map(|file| PlannedFile::new(root, file, &mapping))
+            .collect();
+
+        Self {
+            include: filter.include().to_vec(),
+            exclude: filter.exclude().to_vec(),
+            ignore: Vec::new(),
+            walk: filter.walk_mode(),
+            matching,
+            mapping,
+            files,
+        }
+    }
+
+    /// Reads a plan, rejecting mappings that aren't pairs of GUIDs or that
+    /// remap a GUID twice, since plans may be edited by hand.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let mut plan = crate::load_json::<Self>(path)?;
+
+        let mut seen = HashSet::new();
+        for (src, dst) in &mut plan.mapping {
+            for guid in [&mut *src, &mut *dst] {
+                if !is_simple_guid(guid) {
+                    return Err(Error::InvalidGuid {
+                        path: path.to_owned(),
+                        guid: guid.clone(),
+                    });
+                }
+                guid.make_ascii_lowercase();
+            }
+            if !seen.insert(src.clone()) {
+                return Err(Error::Parse {
+                    path: path.to_owned(),
+                    reason: format!("GUID {} is mapped more than once", src),
+                });
+            }
+        }
+
+        Ok(plan)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        crate::save_json(path, self)
+    }
+
+    /// Which files the plan searched.
+    pub fn file_filter(&self) -> Result<FileFilter, ignore::Error> {
+        let ignore = self.ignore.iter().map(|suffix| format!("*{}", suffix));
+        let filter = FileFilter::new(
+            self.include.clone(),
+            self.exclude.iter().cloned().chain(ignore).collect(),
+        )?;
+        Ok(filter.walk(self.walk))
+    }
+
+    /// The mapping to apply, with entries in the order they were planned.
+    pub fn mapping(&self) -> GuidMapping {
+        GuidMapping::from_pairs(self.mapping.iter().cloned())
+    }
+
+    /// Every entry of the plan, read from `path`, that remaps or maps to a
+    /// protected GUID.
+    pub fn protected(&self, path: &Path, protected: &ProtectedGuids) -> Vec<Error> {
+        self.mapping
+            .iter()
+            .flat_map(|(src, dst)| [src, dst])
+            .filter(|guid| protected.contains_str(guid))
+            .map(|guid| Error::Conflict {
+                path: path.to_owned(),
+                reason: format!("maps protected GUID {}", guid),
+            })
+            .collect()
+    }
+
+    /// Every way `files` differs from what the plan recorded.
+    pub fn changes(&self, root: &Path, files: &[FileReport]) -> Vec<Error> {
+        let mut expected = self
+            .files
+            .iter()
+            .map(|file| (file.path.as_str(), file))
+            .collect::<BTreeMap<_, _>>();
+
+        let change = |path: &str, reason: &str| Error::Conflict {
+            path: root.join(path),
+            reason: reason.to_owned(),
+        };
+
+        let mut changes = Vec::new();
+        for file in files {
+            let file = PlannedFile::new(root, file, &self.mapping);
+            match expected.remove(file.path.as_str()) {
+                None => changes.push(change(&file.path, "now contains mapped GUIDs")),
+                Some(planned) if planned.sha256 != file.sha256 => {
+                    changes.push(change(&file.path, "was modified"))
+                }
+                Some(planned) if *planned != file => {
+                    changes.push(change(&file.path, "has different GUID hits"))
+                }
+                Some(_) => {}
+            }
+        }
+
+        for path in expected.into_keys() {
+            changes.push(change(path, "no longer contains mapped GUIDs"));
+        }
+
+        changes
+    }
+}
+
+impl PlannedFile {
+    fn new(root: &Path, file: &FileReport, mapping: &[(String, String)]) -> Self {
+        Self {
+            path: relative_path(root, &file.path),
+            sha256: file.sha256.clone(),
+            hits: count_by_guid(&file.hits, mapping),
+            skipped: count_by_guid(&file.skipped, mapping),
+        }
+    }
+}
+
+fn count_by_guid(
+    hits: &[(usize, Vec<usize>)],
+    mapping: &[(String, String)],
+) -> BTreeMap<String, usize> {
+    hits.iter()
+        .map(|(n, offsets)| (mapping[*n].0.clone(), offsets.len()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    const A: &str = "0123456789abcdef0123456789abcdef";
+    const B: &str = "fedcba9876543210fedcba9876543210";
+
+    fn file(path: &str, sha256: &str, hits: usize) -> FileReport {
+        FileReport {
+            path: PathBuf::from("/p").join(path),
+            sha256: sha256.to_owned(),
+            rewritten_sha256: None,
+            hits: vec![(0, (0..hits).collect())],
+            skipped: Vec::new(),
+            binary: false,
+            staged: None,
+        }
+    }
+
+    /// The reasons `files` differ from a plan of `a.mat` and `b.mat`.
+    fn changes(files: &[FileReport]) -> Vec<(String, String)> {
+        let root = Path::new("/p");
+        let mapping = GuidMapping::from_pairs([(A.to_owned(), B.to_owned())]);
+        let planned = [file("a.mat", "1", 1), file("b.mat", "2", 2)];
+        let plan = RewritePlan::new(
+            root,
+            &FileFilter::default(),
+            MatchOptions::default(),
+            &mapping,
+            &planned,
+        );
+
+        plan.changes(root, files)
+            .into_iter()
+            .map(|e| match e {
+                Error::Conflict { path, reason } => (relative_path(root, &path), reason),
+                e => panic!("unexpected {}", e),
+            })
+            .collect()
+    }
+
+    fn change(path: &str, reason: &str) -> (String, String) {
+        (path.to_owned(), reason.to_owned())
+    }
+
+    #[test]
+    fn unchanged() {
+        assert!(changes(&[file("a.mat", "1", 1), file("b.mat", "2", 2)]).is_empty());
+    }
+
+    #[test]
+    fn modified() {
+        assert_eq!(
+            changes(&[file("a.mat", "3", 1), file("b.mat", "2", 2)]),
+            [change("a.mat", "was modified")]
+        );
+    }
+
+    #[test]
+    fn new_hit() {
+        assert_eq!(
+            changes(&[
+                file("a.mat", "1", 1),
+                file("b.mat", "2", 2),
+                file("c.mat", "3", 1)
+            ]),
+            [change("c.mat", "now contains mapped GUIDs")]
+        );
+    }
+
+    #[test]
+    fn lost_hit() {
+        assert_eq!(
+            changes(&[file("a.mat", "1", 1)]),
+            [change("b.mat", "no longer contains mapped GUIDs")]
+        );
+    }
+
+    #[test]
+    fn changed_hit_count() {
+        assert_eq!(
+            changes(&[file("a.mat", "1", 1), file("b.mat", "2", 3)]),
+            [change("b.mat", "has different GUID hits")]
+        );
+    }
+}
@@ -0,0 +1,74 @@
+//! GUIDs that must never be remapped, because Unity hardcodes them: every
+//! project references the same built-in resources by the same GUIDs.
+
+use std::{collections::HashSet, path::Path};
+
+use uuid::Uuid;
+
+use crate::Error;
+
+/// The GUIDs of Unity's built-in resources: the default resources, the
+/// built-in extra resources such as the default shaders, the legacy editor
+/// resources, and the null GUID of missing references.
+pub const BUILTIN_GUIDS: &[&str] = &[
+    "0000000000000000e000000000000000",
+    "0000000000000000f000000000000000",
+    "0000000000000000d000000000000000",
+    "00000000000000000000000000000000",
+];
+
+/// The built-in GUIDs plus any a project adds.
+#[derive(Clone, Debug)]
+pub struct ProtectedGuids {
+    guids: HashSet<Uuid>,
+}
+
+impl Default for ProtectedGuids {
+    fn default() -> Self {
+        Self {
+            guids: BUILTIN_GUIDS
+                .iter()
+                .map(|guid| Uuid::parse_str(guid).unwrap())
+                .collect(),
+        }
+    }
+}
+
+impl ProtectedGuids {
+    /// [`BUILTIN_GUIDS`] and `guids`.
+    pub fn new(guids: impl IntoIterator<Item = Uuid>) -> Self {
+        let mut protected = Self::default();
+        protected.guids.extend(guids);
+        protected
+    }
+
+    /// Reads GUIDs from a file holding one per line. Blank lines and lines
+    /// starting with `#` are skipped.
+    pub fn read(path: &Path) -> Result<Vec<Uuid>, Error> {
+        let contents = std::fs::read_to_string(path).map_err(|source| Error::Read {
+            path: path.to_owned(),
+            source,
+        })?;
+
+        contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|guid| {
+                Uuid::parse_str(guid).map_err(|_| Error::InvalidGuid {
+                    path: path.to_owned(),
+                    guid: guid.to_owned(),
+                })
+            })
+            .collect()
+    }
+
+    pub fn contains(&self, guid: &Uuid) -> bool {
+        self.guids.contains(guid)
+    }
+
+    /// Whether `guid`, as hex digits, is protected.
+    pub fn contains_str(&self, guid: &str) -> bool {
+        Uuid::parse_str(guid).is_ok_and(|guid| self.contains(&guid))
+    }
+}
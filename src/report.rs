@@ -0,0 +1,215 @@
+//! Machine-readable descriptions of a run, either as one JSON document or as a
+//! stream of JSON lines, one [`Event`] per line. Paths are relative to the
+//! project root.
+
+use std::path::Path;
+
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    asmdef::AssemblyReference, dangling::DanglingReference, merge::PathCollision, relative_path,
+    Error, FileReport, GuidMapping, MappingEntry,
+};
+
+#[derive(Serialize, Default)]
+pub struct Report {
+    pub mapping: Vec<MappingRecord>,
+    pub files: Vec<FileRecord>,
+    pub errors: Vec<ErrorRecord>,
+    pub unresolved: Vec<UnresolvedRecord>,
+    pub collisions: Vec<CollisionRecord>,
+    pub summary: Summary,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum Event {
+    Mapping(MappingRecord),
+    File(FileRecord),
+    Error(ErrorRecord),
+    Unresolved(UnresolvedRecord),
+    Collision(CollisionRecord),
+    Summary(Summary),
+}
+
+#[derive(Serialize, Clone)]
+pub struct MappingRecord {
+    pub src: String,
+    pub dst: String,
+    /// The .meta file the old GUID was read from.
+    pub meta: Option<String>,
+    /// The folder rewrites are limited to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct FileRecord {
+    pub path: String,
+    pub sha256: String,
+    /// Set when the file was rewritten, rather than only found.
+    pub rewritten_sha256: Option<String>,
+    pub hits: Vec<GuidRecord>,
+    /// Matches left alone because they were not clearly a GUID, or because
+    /// the file is binary.
+    pub skipped: Vec<GuidRecord>,
+    pub binary: bool,
+}
+
+#[derive(Serialize, Clone)]
+pub struct GuidRecord {
+    pub src: String,
+    pub dst: String,
+    pub count: usize,
+    pub offsets: Vec<usize>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ErrorRecord {
+    pub kind: &'static str,
+    pub path: String,
+    pub message: String,
+}
+
+/// A reference to a GUID that neither the mapping nor the project defines.
+#[derive(Serialize, Clone)]
+pub struct UnresolvedRecord {
+    pub path: String,
+    pub guid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    /// The fileID of the object holding the reference.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_id: Option<String>,
+}
+
+/// An asset a merge left out because the destination has one at its path.
+#[derive(Serialize, Clone)]
+pub struct CollisionRecord {
+    pub path: String,
+    pub src_guid: Option<String>,
+    pub dst_guid: Option<String>,
+}
+
+#[derive(Serialize, Clone, Default)]
+pub struct Summary {
+    pub mapped: usize,
+    pub files: usize,
+    pub rewritten: usize,
+    pub hits: usize,
+    pub skipped: usize,
+    pub errors: usize,
+    pub unresolved: usize,
+    pub collisions: usize,
+}
+
+impl MappingRecord {
+    pub fn new(root: &Path, entry: &MappingEntry) -> Self {
+        Self {
+            src: entry.src.clone(),
+            dst: entry.dst.clone(),
+            meta: entry.meta.as_deref().map(|meta| relative_path(root, meta)),
+            scope: entry
+                .scope
+                .as_deref()
+                .map(|scope| relative_path(root, scope)),
+        }
+    }
+}
+
+impl FileRecord {
+    pub fn new(root: &Path, file: &FileReport, mapping: &GuidMapping) -> Self {
+        let records = |hits: &[(usize, Vec<usize>)]| {
+            hits.iter()
+                .map(|(i, offsets)| GuidRecord {
+                    src: mapping[*i].src.clone(),
+                    dst: mapping[*i].dst.clone(),
+                    count: offsets.len(),
+                    offsets: offsets.clone(),
+                })
+                .collect()
+        };
+
+        Self {
+            path: relative_path(root, &file.path),
+            sha256: file.sha256.clone(),
+            rewritten_sha256: file.rewritten_sha256.clone(),
+            hits: records(&file.hits),
+            skipped: records(&file.skipped),
+            binary: file.binary,
+        }
+    }
+}
+
+impl ErrorRecord {
+    pub fn new(root: &Path, error: &Error) -> Self {
+        Self {
+            kind: error.kind(),
+            path: relative_path(root, error.path()),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl UnresolvedRecord {
+    pub fn new(root: &Path, reference: &AssemblyReference) -> Self {
+        Self {
+            path: relative_path(root, &reference.path),
+            guid: reference.guid.clone(),
+            line: None,
+            file_id: None,
+        }
+    }
+
+    pub fn dangling(root: &Path, reference: &DanglingReference) -> Self {
+        Self {
+            path: relative_path(root, &reference.path),
+            guid: reference.guid.clone(),
+            line: Some(reference.line),
+            file_id: reference.file_id.clone(),
+        }
+    }
+}
+
+impl CollisionRecord {
+    pub fn new(collision: &PathCollision) -> Self {
+        let guid = |guid: Option<Uuid>| guid.map(|guid| guid.simple().to_string());
+        Self {
+            path: relative_path(Path::new(""), &collision.path),
+            src_guid: guid(collision.src_guid),
+            dst_guid: guid(collision.dst_guid),
+        }
+    }
+}
+
+impl Report {
+    pub fn add(&mut self, event: &Event) {
+        match event {
+            Event::Mapping(record) => {
+                self.summary.mapped += 1;
+                self.mapping.push(record.clone());
+            }
+            Event::File(record) => {
+                self.summary.files += 1;
+                self.summary.rewritten += record.rewritten_sha256.is_some() as usize;
+                self.summary.hits += record.hits.iter().map(|h| h.count).sum::<usize>();
+                self.summary.skipped += record.skipped.iter().map(|h| h.count).sum::<usize>();
+                self.files.push(record.clone());
+            }
+            Event::Error(record) => {
+                self.summary.errors += 1;
+                self.errors.push(record.clone());
+            }
+            Event::Unresolved(record) => {
+                self.summary.unresolved += 1;
+                self.unresolved.push(record.clone());
+            }
+            Event::Collision(record) => {
+                self.summary.collisions += 1;
+                self.collisions.push(record.clone());
+            }
+            Event::Summary(_) => {}
+        }
+    }
+}
@@ -0,0 +1,637 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use aho_corasick::AhoCorasick;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    asmdef, binary, journal::Journal, serialized, sha256, staging, walk::walk_files, yaml, Error,
+    FileFilter, GuidMapping, UUID_STR_LEN,
+};
+
+/// Which occurrences of a mapped GUID get rewritten.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct MatchOptions {
+    /// Only rewrite GUIDs that follow a `guid:`-style key.
+    #[serde(default)]
+    pub guid_context: bool,
+    #[serde(default)]
+    pub mode: RewriteMode,
+}
+
+/// How files are searched for GUIDs. Assembly definitions are parsed as JSON
+/// in either mode, and only the `GUID:` references in them are rewritten.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RewriteMode {
+    /// Rewrite every standalone occurrence of a GUID in any text file
+    #[default]
+    Text,
+    /// Only rewrite `{fileID, guid, type}` references in Unity YAML files and
+    /// the `guid:` key of .meta files
+    Yaml,
+}
+
+/// The mapping entries found in one file, as `(mapping index, offsets)` pairs.
+#[derive(Debug)]
+pub struct FileReport {
+    pub path: PathBuf,
+    pub sha256: String,
+    /// Hash of the new contents, if the file was rewritten.
+    pub rewritten_sha256: Option<String>,
+    pub hits: Vec<(usize, Vec<usize>)>,
+    /// Matches left alone because they were not clearly a GUID, or because
+    /// the file is binary.
+    pub skipped: Vec<(usize, Vec<usize>)>,
+    /// Whether the file is binary. Only the raw GUIDs in the externals table
+    /// of a Unity SerializedFile are rewritten in binary files.
+    pub binary: bool,
+    /// Where the new contents were staged, waiting to be committed.
+    pub(crate) staged: Option<PathBuf>,
+}
+
+/// What a [`Rewriter`] found, and with `force` rewrote, in a project.
+#[derive(Debug, Default)]
+pub struct RewriteReport {
+    /// Every file containing a mapped GUID, in path order.
+    pub files: Vec<FileReport>,
+    /// Files that could not be searched and were left alone.
+    pub errors: Vec<Error>,
+}
+
+/// Applies a [`GuidMapping`] to every file of a project.
+///
+/// Without [`force`](Rewriter::force) nothing is written, which makes
+/// [`run`](Rewriter::run) a dry run reporting what would change.
+pub struct Rewriter<'a> {
+    mapping: &'a GuidMapping,
+    files: FileFilter,
+    skip: Vec<PathBuf>,
+    matching: MatchOptions,
+    force: bool,
+    partial: bool,
+    journal: Option<PathBuf>,
+}
+
+impl<'a> Rewriter<'a> {
+    pub fn new(mapping: &'a GuidMapping) -> Self {
+        Self {
+            mapping,
+            files: FileFilter::default(),
+            skip: Vec::new(),
+            matching: MatchOptions::default(),
+            force: false,
+            partial: false,
+            journal: None,
+        }
+    }
+
+    /// Which files are searched, by default all of them.
+    pub fn files(mut self, filter: FileFilter) -> Self {
+        self.files = filter;
+        self
+    }
+
+    /// Leaves this one file alone, e.g. a plan stored inside the project.
+    pub fn skip(mut self, path: impl Into<PathBuf>) -> Self {
+        self.skip.push(path.into());
+        self
+    }
+
+    pub fn matching(mut self, matching: MatchOptions) -> Self {
+        self.matching = matching;
+        self
+    }
+
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Commits the rewritten files even if others could not be searched,
+    /// which leaves those referencing the old GUIDs.
+    pub fn partial(mut self, partial: bool) -> Self {
+        self.partial = partial;
+        self
+    }
+
+    /// Records every rewritten file here so a forced run can be reverted. The
+    /// journal itself is never rewritten.
+    pub fn journal(mut self, path: impl Into<PathBuf>) -> Self {
+        self.journal = Some(path.into());
+        self
+    }
+
+    /// Searches every file under `root` for mapped GUIDs.
+    ///
+    /// With `force`, every rewritten file is staged first, then the journal is
+    /// written so even an interrupted run can be reverted, and only then are
+    /// the staged files moved into place. If anything fails before that last
+    /// step, no file is changed. Unless [`partial`](Rewriter::partial) is set,
+    /// that includes a file that could not be read or parsed, which is
+    /// returned as [`Error::Incomplete`].
+    pub fn run(&self, root: &Path) -> Result<RewriteReport, Error> {
+        let matcher = Matcher::new(self.mapping, self.matching);
+
+        let (files, walk_errors) = walk_files(root, &self.files);
+        let results = files
+            .into_par_iter()
+            .filter(|path| !self.skip.contains(path) && self.journal.as_ref() != Some(path))
+            .map(|path| rewrite_file(path, &matcher, self.mapping, self.force))
+            .collect::<Vec<_>>();
+
+        let mut report = RewriteReport {
+            errors: walk_errors,
+            ..Default::default()
+        };
+        let mut stage_error = None;
+        for result in results {
+            match result {
+                Ok(Some(file)) => report.files.push(file),
+                Ok(None) => {}
+                Err(e @ Error::Write { .. }) => {
+                    stage_error.get_or_insert(e);
+                }
+                Err(e) => report.errors.push(e),
+            }
+        }
+
+        let staged = report
+            .files
+            .iter()
+            .filter_map(|file| Some((file.staged.as_deref()?, file.path.as_path())))
+            .collect::<Vec<_>>();
+        let discard = || {
+            for (temp, _) in &staged {
+                staging::discard(temp);
+            }
+        };
+
+        if let Some(e) = stage_error {
+            discard();
+            return Err(e);
+        }
+        if self.force && !self.partial && !report.errors.is_empty() {
+            discard();
+            return Err(Error::Incomplete {
+                path: root.to_owned(),
+                errors: report.errors,
+            });
+        }
+
+        if let Some(path) = self.journal.as_ref().filter(|_| self.force) {
+            let journal = Journal::new(root, self.mapping, &report.files);
+            if let Err(e) = journal.save(path) {
+                discard();
+                return Err(e);
+            }
+        }
+
+        staging::commit_all(staged)?;
+
+        Ok(report)
+    }
+}
+
+impl Rewriter<'_> {
+    /// Like [`run`](Rewriter::run), but over files held in memory as
+    /// `(path, contents)` pairs, which are rewritten in place. The file filter
+    /// and the journal are not used.
+    pub fn run_in_memory(&self, files: &mut [(PathBuf, Vec<u8>)]) -> RewriteReport {
+        let matcher = Matcher::new(self.mapping, self.matching);
+        let results = files
+            .par_iter_mut()
+            .map(|(path, contents)| {
+                rewrite_contents(path.clone(), contents, &matcher, self.mapping, self.force)
+            })
+            .collect::<Vec<_>>();
+
+        let mut report = RewriteReport::default();
+        for result in results {
+            match result {
+                Ok(Some(file)) => report.files.push(file),
+                Ok(None) => {}
+                Err(e) => report.errors.push(e),
+            }
+        }
+
+        report
+    }
+
+    /// Copies each of `paths` from under `src` to the same path under `dst`,
+    /// rewriting the copies, which are reported by their new path. Like in
+    /// [`run`](Rewriter::run), nothing is written without `force`, and with
+    /// it every copy is staged before any is moved into place, including when
+    /// a file could not be read unless [`partial`](Rewriter::partial) is set.
+    /// Missing folders are created, and removed again if nothing is copied.
+    pub fn copy(&self, src: &Path, dst: &Path, paths: &[PathBuf]) -> Result<RewriteReport, Error> {
+        let files = paths
+            .iter()
+            .map(|path| (src.join(path), dst.join(path)))
+            .collect::<Vec<_>>();
+        let created = missing_dirs(files.iter().filter_map(|(_, to)| to.parent()));
+
+        let matcher = Matcher::new(self.mapping, self.matching);
+        let results = files
+            .par_iter()
+            .map(|(from, to)| copy_file(from, to, &matcher, self.mapping, self.force))
+            .collect::<Vec<_>>();
+
+        let mut report = RewriteReport::default();
+        let mut staged = Vec::new();
+        let mut stage_error = None;
+        for ((_, to), result) in files.iter().zip(results) {
+            match result {
+                Ok((file, temp)) => {
+                    report.files.extend(file);
+                    staged.extend(temp.map(|temp| (temp, to.as_path())));
+                }
+                Err(e @ Error::Write { .. }) => {
+                    stage_error.get_or_insert(e);
+                }
+                Err(e) => report.errors.push(e),
+            }
+        }
+
+        let discard = || {
+            for (temp, _) in &staged {
+                staging::discard(temp);
+            }
+            // Deepest first, so each is empty by the time it is removed.
+            for dir in created.iter().rev() {
+                let _ = std::fs::remove_dir(dir);
+            }
+        };
+
+        if let Some(e) = stage_error {
+            discard();
+            return Err(e);
+        }
+        if self.force && !self.partial && !report.errors.is_empty() {
+            discard();
+            return Err(Error::Incomplete {
+                path: src.to_owned(),
+                errors: report.errors,
+            });
+        }
+
+        staging::commit_all(staged.iter().map(|(temp, to)| (temp.as_path(), *to)))?;
+
+        Ok(report)
+    }
+}
+
+/// The folders among `dirs` and their ancestors that don't exist yet, parents
+/// first.
+fn missing_dirs<'a>(dirs: impl Iterator<Item = &'a Path>) -> Vec<PathBuf> {
+    let mut missing = std::collections::BTreeSet::new();
+    for dir in dirs {
+        for dir in dir.ancestors() {
+            if missing.contains(dir) || dir.exists() {
+                break;
+            }
+            missing.insert(dir.to_owned());
+        }
+    }
+    missing.into_iter().collect()
+}
+
+/// `(mapping index, offset)` pairs.
+type Matches = Vec<(usize, usize)>;
+
+/// Finds the source GUIDs of a mapping in file contents.
+struct Matcher<'a> {
+    /// One automaton over every source GUID, so each file is scanned once no
+    /// matter how large the mapping is.
+    automaton: AhoCorasick,
+    /// The same patterns in the raw form binary files store GUIDs in.
+    raw: AhoCorasick,
+    /// The mapping entries for each pattern, innermost scope first.
+    entries: Vec<Vec<usize>>,
+    mapping: &'a GuidMapping,
+    /// When set, only matches preceded by this are rewritten.
+    context: Option<regex::bytes::Regex>,
+    mode: RewriteMode,
+}
+
+impl<'a> Matcher<'a> {
+    fn new(mapping: &'a GuidMapping, matching: MatchOptions) -> Self {
+        let mut patterns = Vec::new();
+        let mut entries = Vec::<Vec<usize>>::new();
+        let mut pattern_of = HashMap::new();
+        for (i, entry) in mapping.entries().iter().enumerate() {
+            let n = *pattern_of.entry(&entry.src).or_insert_with(|| {
+                patterns.push(&entry.src);
+                entries.push(Vec::new());
+                patterns.len() - 1
+            });
+            entries[n].push(i);
+        }
+
+        for entries in &mut entries {
+            entries.sort_by_key(|&i| {
+                let depth = mapping[i].scope.as_ref().map(|s| s.components().count());
+                std::cmp::Reverse(depth.unwrap_or(0))
+            });
+        }
+
+        let raw = AhoCorasick::new(patterns.iter().map(|src| binary::raw_guid(src))).unwrap();
+        let automaton = AhoCorasick::new(patterns).unwrap();
+        let context = matching.guid_context.then(|| {
+            // A key ending in "guid", optionally quoted or escaped, then a
+            // colon and an optional opening quote: `guid: `, `"GUID:`,
+            // `m_AssetGUID: `, `"guid": "`, `\"guid\":\"`.
+            regex::bytes::Regex::new(r#"(?i)guid\\?["']?[ \t]*:[ \t]*\\?["']?$"#).unwrap()
+        });
+
+        Self {
+            automaton,
+            raw,
+            entries,
+            mapping,
+            context,
+            mode: matching.mode,
+        }
+    }
+
+    /// The mapping entry that applies to `pattern` in the file at `path`.
+    fn entry(&self, pattern: usize, path: &Path) -> Option<usize> {
+        self.entries[pattern].iter().copied().find(|&i| {
+            self.mapping[i]
+                .scope
+                .as_ref()
+                .is_none_or(|scope| path.starts_with(scope))
+        })
+    }
+
+    /// Returns the matches to rewrite and the ones too ambiguous to touch.
+    fn find(&self, path: &Path, contents: &str) -> Result<(Matches, Matches), String> {
+        let (mut matches, mut skipped) = self.find_text(path, contents.as_bytes());
+        if matches.is_empty() {
+            return Ok((matches, skipped));
+        }
+
+        let is_meta = path.extension().is_some_and(|ext| ext == "meta");
+        let offsets = if asmdef::is_assembly_file(path) {
+            asmdef::guid_offsets(contents)?
+                .into_iter()
+                .map(|(n, _)| n)
+                .collect::<HashSet<_>>()
+        } else if self.mode == RewriteMode::Text {
+            return Ok((matches, skipped));
+        } else if is_meta || yaml::is_unity_yaml(contents.as_bytes()) {
+            yaml::guid_offsets(contents, is_meta)?
+                .into_iter()
+                .collect::<HashSet<_>>()
+        } else {
+            skipped.append(&mut matches);
+            return Ok((matches, skipped));
+        };
+        let (matches, mut outside) = matches
+            .into_iter()
+            .partition::<Vec<_>, _>(|(_, n)| offsets.contains(n));
+        skipped.append(&mut outside);
+
+        Ok((matches, skipped))
+    }
+
+    fn find_text(&self, path: &Path, contents: &[u8]) -> (Matches, Matches) {
+        let is_hex = |n: Option<&u8>| n.is_some_and(u8::is_ascii_hexdigit);
+
+        self.automaton
+            .find_iter(contents)
+            .filter_map(|m| Some((self.entry(m.pattern().as_usize(), path)?, m.start())))
+            .partition(|&(_, n)| {
+                // Part of a longer hex string, e.g. a hash or texture data.
+                if is_hex(n.checked_sub(1).and_then(|n| contents.get(n)))
+                    || is_hex(contents.get(n + UUID_STR_LEN))
+                {
+                    return false;
+                }
+
+                self.context.as_ref().is_none_or(|context| {
+                    context.is_match(&contents[n.saturating_sub(UUID_STR_LEN)..n])
+                })
+            })
+    }
+}
+
+impl Matcher<'_> {
+    /// Returns the raw GUIDs to rewrite in a binary file, which are the ones in
+    /// the externals table of a SerializedFile, and every other occurrence of
+    /// a mapped GUID, as hex or raw bytes.
+    fn find_binary(&self, path: &Path, contents: &[u8]) -> Result<(Matches, Matches), String> {
+        let to_match =
+            |m: aho_corasick::Match| Some((self.entry(m.pattern().as_usize(), path)?, m.start()));
+        let mut skipped = self
+            .automaton
+            .find_overlapping_iter(contents)
+            .filter_map(to_match)
+            .collect::<Vec<_>>();
+        let raw = self
+            .raw
+            .find_overlapping_iter(contents)
+            .filter_map(to_match)
+            .collect::<Vec<_>>();
+
+        let Some(header) = serialized::Header::parse(contents) else {
+            skipped.extend(raw);
+            return Ok((Vec::new(), skipped));
+        };
+
+        let offsets = serialized::externals(&header, contents)?
+            .into_iter()
+            .map(|external| external.offset)
+            .collect::<HashSet<_>>();
+        let (matches, mut outside) = raw
+            .into_iter()
+            .partition::<Vec<_>, _>(|(_, n)| offsets.contains(n));
+        skipped.append(&mut outside);
+
+        Ok((matches, skipped))
+    }
+}
+
+fn group_by_mapping(mut matches: Matches) -> Vec<(usize, Vec<usize>)> {
+    matches.sort_unstable();
+    matches
+        .chunk_by(|a, b| a.0 == b.0)
+        .map(|group| (group[0].0, group.iter().map(|&(_, n)| n).collect()))
+        .collect()
+}
+
+fn rewrite_file(
+    path: PathBuf,
+    matcher: &Matcher,
+    mapping: &GuidMapping,
+    force: bool,
+) -> Result<Option<FileReport>, Error> {
+    let mut contents = match std::fs::read(&path) {
+        Ok(contents) => contents,
+        Err(source) => return Err(Error::Read { path, source }),
+    };
+
+    let Some(mut file) = rewrite_contents(path, &mut contents, matcher, mapping, force)? else {
+        return Ok(None);
+    };
+
+    if file.rewritten_sha256.is_some() {
+        match staging::stage(&file.path, &contents) {
+            Ok(temp) => file.staged = Some(temp),
+            Err(source) => {
+                return Err(Error::Write {
+                    path: file.path,
+                    source,
+                })
+            }
+        }
+    }
+
+    Ok(Some(file))
+}
+
+/// Reads `from` and, with `force`, stages its rewritten contents at `to`.
+fn copy_file(
+    from: &Path,
+    to: &Path,
+    matcher: &Matcher,
+    mapping: &GuidMapping,
+    force: bool,
+) -> Result<(Option<FileReport>, Option<PathBuf>), Error> {
+    let mut contents = std::fs::read(from).map_err(|source| Error::Read {
+        path: from.to_owned(),
+        source,
+    })?;
+
+    let file = rewrite_contents(to.to_owned(), &mut contents, matcher, mapping, force)?;
+    if !force {
+        return Ok((file, None));
+    }
+
+    let write = |source| Error::Write {
+        path: to.to_owned(),
+        source,
+    };
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent).map_err(write)?;
+    }
+    let temp = staging::stage(to, &contents).map_err(write)?;
+
+    Ok((file, Some(temp)))
+}
+
+/// Searches `contents` of the file at `path`, rewriting them in place if
+/// `force` is set.
+fn rewrite_contents(
+    path: PathBuf,
+    contents: &mut [u8],
+    matcher: &Matcher,
+    mapping: &GuidMapping,
+    force: bool,
+) -> Result<Option<FileReport>, Error> {
+    let binary = binary::is_binary(contents);
+    let found = if binary {
+        matcher.find_binary(&path, contents)
+    } else {
+        // Checked by `is_binary`.
+        matcher.find(&path, std::str::from_utf8(contents).unwrap())
+    };
+    let (matches, skipped) = match found {
+        Ok(found) => found,
+        Err(reason) => return Err(Error::Parse { path, reason }),
+    };
+    if matches.is_empty() && skipped.is_empty() {
+        return Ok(None);
+    }
+
+    let sha256 = sha256(contents);
+    let hits = group_by_mapping(matches);
+    let skipped = group_by_mapping(skipped);
+
+    if force {
+        for (i, offsets) in &hits {
+            let dst = &mapping[*i].dst;
+            let raw = binary::raw_guid(dst);
+            let dst = if binary { &raw[..] } else { dst.as_bytes() };
+            for &n in offsets {
+                contents[n..(n + dst.len())].copy_from_slice(dst);
+            }
+        }
+    }
+
+    let rewritten_sha256 = (force && !hits.is_empty()).then(|| crate::sha256(contents));
+
+    Ok(Some(FileReport {
+        path,
+        sha256,
+        rewritten_sha256,
+        hits,
+        skipped,
+        binary,
+        staged: None,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A: &str = "0123456789abcdef0123456789abcdef";
+    const B: &str = "fedcba9876543210fedcba9876543210";
+
+    /// The offsets of `A` that would be rewritten in `contents`, and those
+    /// that would be skipped.
+    fn find(contents: &str, guid_context: bool) -> (Vec<usize>, Vec<usize>) {
+        let mapping = GuidMapping::from_pairs([(A.to_owned(), B.to_owned())]);
+        let matching = MatchOptions {
+            guid_context,
+            mode: RewriteMode::Text,
+        };
+        let (matches, skipped) =
+            Matcher::new(&mapping, matching).find_text(Path::new("f.txt"), contents.as_bytes());
+        let offsets = |matches: Matches| matches.into_iter().map(|(_, n)| n).collect();
+        (offsets(matches), offsets(skipped))
+    }
+
+    #[test]
+    fn guids_inside_longer_hex_are_skipped() {
+        let hash = format!("hash: {}{}\n", A, A);
+        assert_eq!(find(&hash, false), (vec![], vec![6, 38]));
+
+        let prefixed = format!("id: f{}\n", A);
+        assert_eq!(find(&prefixed, false), (vec![], vec![5]));
+
+        let standalone = format!("id: {}\n", A);
+        assert_eq!(find(&standalone, false), (vec![4], vec![]));
+    }
+
+    #[test]
+    fn guid_context() {
+        for prefix in [
+            "guid: ",
+            "{fileID: 1, guid: ",
+            "\"GUID:",
+            "m_AssetGUID: ",
+            "\\\"guid\\\":\\\"",
+            "\"guid\": \"",
+        ] {
+            let contents = format!("{}{}", prefix, A);
+            assert_eq!(
+                find(&contents, true),
+                (vec![prefix.len()], vec![]),
+                "{}",
+                prefix
+            );
+        }
+    }
+
+    #[test]
+    fn bare_guids_are_skipped_with_guid_context() {
+        let contents = format!("m_Name: {}\n{}\n", A, A);
+        assert_eq!(find(&contents, true), (vec![], vec![8, 41]));
+        assert_eq!(find(&contents, false), (vec![8, 41], vec![]));
+    }
+}
@@ -0,0 +1,460 @@
+//! Unity's binary SerializedFile format, used by `.asset`, `.prefab` and
+//! `.unity` files when Asset Serialization is set to Mixed or Force Binary.
+//!
+//! Other assets are referenced through the externals table at the end of the
+//! metadata, which holds each referenced file's GUID as 16 raw bytes. Finding
+//! it means walking everything before it: the type tree, the object table and
+//! the script types, whose layouts changed across format versions. Versions 9
+//! (Unity 3.5) through 22 (Unity 2020 and later) are supported.
+
+/// The fixed-size header every SerializedFile starts with. All fields are
+/// big-endian; version 22 moved the sizes into wider fields after the
+/// original ones, which are left zero.
+#[derive(Clone, Copy, Debug)]
+pub struct Header {
+    pub version: u32,
+    pub metadata_size: u64,
+    pub file_size: u64,
+    pub data_offset: u64,
+}
+
+impl Header {
+    /// The header of `contents`, if they look like a SerializedFile: a
+    /// plausible version and sizes consistent with the file's length.
+    pub fn parse(contents: &[u8]) -> Option<Self> {
+        let u32_at = |n: usize| Some(u32::from_be_bytes(contents.get(n..n + 4)?.try_into().ok()?));
+        let u64_at = |n: usize| Some(u64::from_be_bytes(contents.get(n..n + 8)?.try_into().ok()?));
+
+        let version = u32_at(8)?;
+        let header = match version {
+            9..=21 => Self {
+                version,
+                metadata_size: u32_at(0)?.into(),
+                file_size: u32_at(4)?.into(),
+                data_offset: u32_at(12)?.into(),
+            },
+            22 => Self {
+                version,
+                metadata_size: u32_at(20)?.into(),
+                file_size: u64_at(24)?,
+                data_offset: u64_at(32)?,
+            },
+            _ => return None,
+        };
+
+        let len = contents.len() as u64;
+        (header.file_size == len
+            && header.data_offset <= len
+            && header.metadata_size > 0
+            && header.metadata_size < len)
+            .then_some(header)
+    }
+
+    /// Where the metadata starts, right after the header.
+    fn metadata_offset(&self) -> usize {
+        if self.version >= 22 {
+            48
+        } else {
+            20
+        }
+    }
+}
+
+/// A file referenced by a SerializedFile.
+#[derive(Clone, Debug)]
+pub struct External {
+    /// Where the 16 GUID bytes are in the file.
+    pub offset: usize,
+    pub guid: [u8; 16],
+    pub path: String,
+}
+
+/// The externals table of the SerializedFile `contents`.
+pub fn externals(header: &Header, contents: &[u8]) -> Result<Vec<External>, String> {
+    let version = header.version;
+    let mut reader = Reader {
+        contents,
+        pos: 16,
+        big_endian: true,
+    };
+    reader.big_endian = reader.u8()? != 0;
+    reader.pos = header.metadata_offset();
+
+    if version >= 7 {
+        reader.string()?;
+    }
+    if version >= 8 {
+        reader.i32()?;
+    }
+    // Type trees became optional in version 13.
+    let type_tree = version < 13 || reader.u8()? != 0;
+
+    for _ in 0..reader.count()? {
+        reader.serialized_type(version, type_tree)?;
+    }
+
+    let big_ids = (7..14).contains(&version) && reader.i32()? != 0;
+    for _ in 0..reader.count()? {
+        reader.object(version, big_ids)?;
+    }
+
+    if version >= 11 {
+        for _ in 0..reader.count()? {
+            reader.i32()?;
+            if version >= 14 {
+                reader.align();
+                reader.skip(8)?;
+            } else {
+                reader.i32()?;
+            }
+        }
+    }
+
+    let mut externals = Vec::new();
+    for _ in 0..reader.count()? {
+        if version >= 6 {
+            reader.string()?;
+        }
+        let offset = reader.pos;
+        let guid = reader.bytes(16)?.try_into().unwrap();
+        reader.i32()?;
+        let path = reader.string()?;
+        externals.push(External { offset, guid, path });
+    }
+
+    Ok(externals)
+}
+
+struct Reader<'a> {
+    contents: &'a [u8],
+    pos: usize,
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .contents
+            .get(self.pos..self.pos.saturating_add(len))
+            .ok_or_else(|| format!("metadata ends early at offset {}", self.pos))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), String> {
+        self.bytes(len).map(drop)
+    }
+
+    fn align(&mut self) {
+        self.pos = self.pos.next_multiple_of(4);
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn i16(&mut self) -> Result<i16, String> {
+        let bytes = self.bytes(2)?.try_into().unwrap();
+        Ok(match self.big_endian {
+            true => i16::from_be_bytes(bytes),
+            false => i16::from_le_bytes(bytes),
+        })
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        let bytes = self.bytes(4)?.try_into().unwrap();
+        Ok(match self.big_endian {
+            true => i32::from_be_bytes(bytes),
+            false => i32::from_le_bytes(bytes),
+        })
+    }
+
+    /// A table length, which must fit in what is left of the file.
+    fn count(&mut self) -> Result<usize, String> {
+        let pos = self.pos;
+        usize::try_from(self.i32()?)
+            .ok()
+            .filter(|&count| count <= self.contents.len() - self.pos)
+            .ok_or_else(|| format!("invalid table length at offset {}", pos))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let rest = &self.contents[self.pos.min(self.contents.len())..];
+        let len = rest
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| format!("unterminated string at offset {}", self.pos))?;
+        let string = String::from_utf8_lossy(&rest[..len]).into_owned();
+        self.pos += len + 1;
+        Ok(string)
+    }
+
+    fn serialized_type(&mut self, version: u32, type_tree: bool) -> Result<(), String> {
+        let class_id = self.i32()?;
+        if version >= 16 {
+            self.u8()?;
+        }
+        if version >= 17 {
+            self.i16()?;
+        }
+        if version >= 13 {
+            // Scripts, i.e. MonoBehaviour, also store their script's hash.
+            if (version < 16 && class_id < 0) || (version >= 16 && class_id == 114) {
+                self.skip(16)?;
+            }
+            self.skip(16)?;
+        }
+
+        if type_tree {
+            if version >= 12 || version == 10 {
+                let nodes = self.count()?;
+                let strings = self.count()?;
+                let node_size = if version >= 19 { 32 } else { 24 };
+                self.skip(nodes * node_size + strings)?;
+            } else {
+                self.legacy_type_tree(version)?;
+            }
+
+            if version >= 21 {
+                let dependencies = self.count()?;
+                self.skip(dependencies * 4)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// A type tree node and its children, as written before version 12.
+    fn legacy_type_tree(&mut self, version: u32) -> Result<(), String> {
+        self.string()?;
+        self.string()?;
+        self.i32()?;
+        if version == 2 {
+            self.i32()?;
+        }
+        if version != 3 {
+            self.i32()?;
+        }
+        self.i32()?;
+        self.i32()?;
+        if version != 3 {
+            self.i32()?;
+        }
+
+        for _ in 0..self.count()? {
+            self.legacy_type_tree(version)?;
+        }
+
+        Ok(())
+    }
+
+    fn object(&mut self, version: u32, big_ids: bool) -> Result<(), String> {
+        if big_ids {
+            self.skip(8)?;
+        } else if version < 14 {
+            self.i32()?;
+        } else {
+            self.align();
+            self.skip(8)?;
+        }
+
+        self.skip(if version >= 22 { 8 } else { 4 })?;
+        self.skip(4 + 4)?;
+        if version < 16 {
+            self.skip(2)?;
+        }
+        if version < 11 {
+            self.skip(2)?;
+        }
+        if (11..17).contains(&version) {
+            self.i16()?;
+        }
+        if version == 15 || version == 16 {
+            self.u8()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GUID: [u8; 16] = *b"\x01\x23\x45\x67\x89\xab\xcd\xef\xfe\xdc\xba\x98\x76\x54\x32\x10";
+
+    /// Writes metadata in the file's byte order, tracking its absolute offset.
+    struct Writer {
+        bytes: Vec<u8>,
+        big_endian: bool,
+    }
+
+    impl Writer {
+        fn i16(&mut self, n: i16) {
+            let bytes = match self.big_endian {
+                true => n.to_be_bytes(),
+                false => n.to_le_bytes(),
+            };
+            self.bytes.extend(bytes);
+        }
+
+        fn i32(&mut self, n: i32) {
+            let bytes = match self.big_endian {
+                true => n.to_be_bytes(),
+                false => n.to_le_bytes(),
+            };
+            self.bytes.extend(bytes);
+        }
+
+        fn zeros(&mut self, len: usize) {
+            self.bytes.resize(self.bytes.len() + len, 0);
+        }
+
+        fn string(&mut self, s: &str) {
+            self.bytes.extend(s.as_bytes());
+            self.bytes.push(0);
+        }
+
+        fn align(&mut self) {
+            self.bytes.resize(self.bytes.len().next_multiple_of(4), 0);
+        }
+    }
+
+    /// A SerializedFile with one script type, one object, one script type
+    /// reference and one external, laid out as Unity writes `version`.
+    /// Returns it with the offset of the external's GUID.
+    fn fixture(version: u32, big_endian: bool) -> (Vec<u8>, usize) {
+        let header_size = if version >= 22 { 48 } else { 20 };
+        let mut w = Writer {
+            bytes: vec![0; header_size],
+            big_endian,
+        };
+
+        w.string("2019.4.0f1");
+        w.i32(19);
+        if version >= 13 {
+            w.bytes.push(1);
+        }
+
+        w.i32(1);
+        let script = (13..16).contains(&version);
+        w.i32(if script { -1 } else { 114 });
+        if version >= 16 {
+            w.bytes.push(0);
+        }
+        if version >= 17 {
+            w.i16(0);
+        }
+        if version >= 13 {
+            w.zeros(if version >= 16 || script { 32 } else { 16 });
+        }
+        if version >= 12 || version == 10 {
+            w.i32(1);
+            w.i32(4);
+            w.zeros(if version >= 19 { 32 } else { 24 } + 4);
+        } else {
+            w.string("Base");
+            w.string("Base");
+            w.i32(-1);
+            w.i32(0);
+            w.i32(0);
+            w.i32(0);
+            w.i32(0);
+            w.i32(0);
+        }
+        if version >= 21 {
+            w.i32(0);
+        }
+
+        if (7..14).contains(&version) {
+            w.i32(0);
+        }
+        w.i32(1);
+        if version >= 14 {
+            w.align();
+            w.zeros(8);
+        } else {
+            w.i32(1);
+        }
+        w.zeros(if version >= 22 { 8 } else { 4 });
+        w.i32(4);
+        w.i32(0);
+        if version < 16 {
+            w.zeros(2);
+        }
+        if version < 11 {
+            w.zeros(2);
+        }
+        if (11..17).contains(&version) {
+            w.i16(0);
+        }
+        if version == 15 || version == 16 {
+            w.bytes.push(0);
+        }
+
+        if version >= 11 {
+            w.i32(1);
+            w.i32(0);
+            if version >= 14 {
+                w.align();
+                w.zeros(8);
+            } else {
+                w.i32(1);
+            }
+        }
+
+        w.i32(1);
+        w.string("");
+        let offset = w.bytes.len();
+        w.bytes.extend(GUID);
+        w.i32(3);
+        w.string("Assets/Other.asset");
+
+        let metadata_size = (w.bytes.len() - header_size) as u32;
+        let data_offset = w.bytes.len() as u32;
+        w.zeros(4);
+        let file_size = w.bytes.len() as u32;
+
+        let mut bytes = w.bytes;
+        bytes[8..12].copy_from_slice(&version.to_be_bytes());
+        bytes[16] = big_endian.into();
+        if version >= 22 {
+            bytes[20..24].copy_from_slice(&metadata_size.to_be_bytes());
+            bytes[24..32].copy_from_slice(&u64::from(file_size).to_be_bytes());
+            bytes[32..40].copy_from_slice(&u64::from(data_offset).to_be_bytes());
+        } else {
+            bytes[0..4].copy_from_slice(&metadata_size.to_be_bytes());
+            bytes[4..8].copy_from_slice(&file_size.to_be_bytes());
+            bytes[12..16].copy_from_slice(&data_offset.to_be_bytes());
+        }
+
+        (bytes, offset)
+    }
+
+    #[test]
+    fn externals_of_every_version() {
+        for version in 9..=22 {
+            for big_endian in [false, true] {
+                let (contents, offset) = fixture(version, big_endian);
+                let header = Header::parse(&contents)
+                    .unwrap_or_else(|| panic!("v{} header not recognized", version));
+                let externals = externals(&header, &contents)
+                    .unwrap_or_else(|e| panic!("v{} big_endian={}: {}", version, big_endian, e));
+
+                assert_eq!(externals.len(), 1, "v{}", version);
+                assert_eq!(externals[0].offset, offset, "v{}", version);
+                assert_eq!(externals[0].guid, GUID, "v{}", version);
+                assert_eq!(externals[0].path, "Assets/Other.asset", "v{}", version);
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_versions_are_not_serialized_files() {
+        for version in [8u32, 23] {
+            let (mut contents, _) = fixture(22, false);
+            contents[8..12].copy_from_slice(&version.to_be_bytes());
+            assert!(Header::parse(&contents).is_none(), "v{}", version);
+        }
+    }
+}
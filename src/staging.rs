@@ -0,0 +1,74 @@
+//! Two-phase writes: new contents are first staged in a temporary file next to
+//! their target, and only renamed over it once every file has been staged, so
+//! a failure part way through never leaves a half-rewritten project behind.
+
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::Error;
+
+/// Writes `contents` to a temporary file beside `target` and returns its path.
+pub fn stage(target: &Path, contents: &[u8]) -> io::Result<PathBuf> {
+    let mut name = OsString::from(".");
+    name.push(target.file_name().unwrap_or_default());
+    name.push(".guid-rewriter.tmp");
+    let temp = target.with_file_name(name);
+
+    let result = (|| {
+        let mut file = File::create(&temp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+
+        if let Ok(metadata) = std::fs::metadata(target) {
+            std::fs::set_permissions(&temp, metadata.permissions())?;
+        }
+
+        Ok(())
+    })();
+
+    match result {
+        Ok(()) => Ok(temp),
+        Err(e) => {
+            discard(&temp);
+            Err(e)
+        }
+    }
+}
+
+/// Atomically replaces `target` with the staged `temp` file.
+pub fn commit(temp: &Path, target: &Path) -> io::Result<()> {
+    std::fs::rename(temp, target)
+}
+
+pub fn discard(temp: &Path) {
+    let _ = std::fs::remove_file(temp);
+}
+
+/// Commits every `(temp, target)` pair in order, stopping at the first
+/// failure and discarding whatever was not committed yet.
+pub fn commit_all<'a>(
+    staged: impl IntoIterator<Item = (&'a Path, &'a Path)>,
+) -> Result<usize, Error> {
+    let mut staged = staged.into_iter();
+    let mut committed = 0;
+
+    while let Some((temp, target)) = staged.next() {
+        if let Err(source) = commit(temp, target) {
+            discard(temp);
+            for (temp, _) in staged {
+                discard(temp);
+            }
+            return Err(Error::Write {
+                path: target.to_owned(),
+                source,
+            });
+        }
+        committed += 1;
+    }
+
+    Ok(committed)
+}
@@ -0,0 +1,102 @@
+//! The project a package or folder is about to be merged into, so only the
+//! GUIDs that would clash with its assets need to be remapped.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use uuid::Uuid;
+
+use crate::{read_metas, Error, Meta, MetaFilter, WalkMode};
+
+/// A project and the .meta files using each GUID in it.
+pub struct Target {
+    root: PathBuf,
+    guids: HashMap<Uuid, Vec<PathBuf>>,
+}
+
+impl Target {
+    /// Reads every .meta under `root`, including those of packages. Also
+    /// returns the ones that could not be read.
+    pub fn read(root: &Path) -> (Self, Vec<Error>) {
+        let (metas, errors) = read_metas(&[root.to_owned()], &MetaFilter::default(), WalkMode::All);
+
+        let mut guids = HashMap::<_, Vec<_>>::new();
+        for meta in metas {
+            guids.entry(meta.guid).or_default().push(meta.path);
+        }
+        let target = Self {
+            root: root.to_owned(),
+            guids,
+        };
+        (target, errors)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// How many .meta files the project has.
+    pub fn meta_count(&self) -> usize {
+        self.guids.values().map(Vec::len).sum()
+    }
+
+    /// Whether another asset of the target uses the GUID of `meta`. One at
+    /// the same path, relative to the first of `roots` holding `meta`, is the
+    /// same asset, e.g. an earlier import of a package.
+    pub fn collides(&self, meta: &Meta, roots: &[PathBuf]) -> bool {
+        let path = roots
+            .iter()
+            .find_map(|root| meta.path.strip_prefix(root).ok())
+            .unwrap_or(&meta.path);
+        self.guids.get(&meta.guid).is_some_and(|paths| {
+            paths
+                .iter()
+                .any(|other| other.strip_prefix(&self.root).unwrap_or(other) != path)
+        })
+    }
+
+    /// The `metas` that collide with the target, see [`collides`](Self::collides).
+    pub fn colliding(&self, metas: &[Meta], roots: &[PathBuf]) -> Vec<Meta> {
+        metas
+            .iter()
+            .filter(|meta| self.collides(meta, roots))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target() -> Target {
+        let guid = Uuid::parse_str("11111111111111111111111111111111").unwrap();
+        Target {
+            root: PathBuf::from("/Tgt"),
+            guids: HashMap::from([(guid, vec![PathBuf::from("/Tgt/Assets/V/a.cs.meta")])]),
+        }
+    }
+
+    fn meta(path: &str) -> Meta {
+        Meta {
+            path: PathBuf::from(path),
+            guid: Uuid::parse_str("11111111111111111111111111111111").unwrap(),
+        }
+    }
+
+    #[test]
+    fn same_path_does_not_collide() {
+        let roots = [PathBuf::from("/Pkg")];
+        assert!(!target().collides(&meta("/Pkg/Assets/V/a.cs.meta"), &roots));
+        assert!(!target().collides(&meta("Assets/V/a.cs.meta"), &[]));
+    }
+
+    #[test]
+    fn different_path_collides() {
+        let roots = [PathBuf::from("/Pkg")];
+        assert!(target().collides(&meta("/Pkg/Assets/W/a.cs.meta"), &roots));
+        assert!(target().collides(&meta("Assets/W/a.cs.meta"), &[]));
+    }
+}
@@ -0,0 +1,298 @@
+//! Which files under a project are searched for GUIDs, as gitignore-style
+//! patterns relative to the project root. Excluded directories are pruned
+//! rather than walked, so `.git` or `Library` cost nothing. In
+//! [`WalkMode::Unity`] the walk also skips what Unity itself would not import
+//! and what the project's .gitignore files leave out.
+
+use std::{
+    ffi::OsStr,
+    io,
+    path::{Path, PathBuf},
+};
+
+use ignore::{
+    gitignore::{Gitignore, GitignoreBuilder},
+    WalkBuilder,
+};
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::Error;
+
+/// What a Unity project holds besides text assets: version control, IDE and
+/// editor state, build output, and binary formats that never contain a text
+/// GUID reference.
+pub const DEFAULT_EXCLUDE: &[&str] = &[
+    ".git/",
+    ".svn/",
+    ".hg/",
+    ".vs/",
+    ".idea/",
+    "/Library/",
+    "/Temp/",
+    "/Obj/",
+    "/Logs/",
+    "/UserSettings/",
+    "/MemoryCaptures/",
+    "/Build/",
+    "/Builds/",
+    "*.png",
+    "*.jpg",
+    "*.jpeg",
+    "*.tga",
+    "*.psd",
+    "*.tif",
+    "*.tiff",
+    "*.exr",
+    "*.hdr",
+    "*.fbx",
+    "*.exe",
+    "*.dll",
+    "*.so",
+    "*.dylib",
+    "*.wav",
+    "*.mp3",
+    "*.ogg",
+    "*.mp4",
+    "*.ttf",
+    "*.otf",
+    "*.zip",
+];
+
+/// Which files a walk visits before any pattern is applied.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WalkMode {
+    /// Every file
+    #[default]
+    All,
+    /// Only what Unity imports: skip files matched by .gitignore and .ignore
+    /// files, and hidden (`.name`), backup (`name~`), `cvs` and `*.tmp` files
+    /// and folders
+    Unity,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct FileFilter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+    mode: WalkMode,
+}
+
+impl FileFilter {
+    /// Searches only files matching one of `include`, or every file if it is
+    /// empty, except those matching `exclude`. Like in .gitignore, a trailing
+    /// `/` only matches directories, a leading `/` anchors a pattern to the
+    /// root, and `!` re-includes what an earlier pattern excluded.
+    pub fn new(include: Vec<String>, exclude: Vec<String>) -> Result<Self, ignore::Error> {
+        let filter = Self {
+            include,
+            exclude,
+            mode: WalkMode::All,
+        };
+        filter.matcher(Path::new(""))?;
+        Ok(filter)
+    }
+
+    /// [`DEFAULT_EXCLUDE`] followed by `exclude`.
+    pub fn with_default_exclude(
+        include: Vec<String>,
+        exclude: Vec<String>,
+    ) -> Result<Self, ignore::Error> {
+        let default = DEFAULT_EXCLUDE.iter().map(|&pattern| pattern.to_owned());
+        Self::new(include, default.chain(exclude).collect())
+    }
+
+    /// Walks in `mode`, [`WalkMode::All`] by default.
+    pub fn walk(mut self, mode: WalkMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn walk_mode(&self) -> WalkMode {
+        self.mode
+    }
+
+    pub fn include(&self) -> &[String] {
+        &self.include
+    }
+
+    pub fn exclude(&self) -> &[String] {
+        &self.exclude
+    }
+
+    /// Whether the file at `path` under `root` is kept by the patterns,
+    /// including those excluding one of its folders. The walk mode is not
+    /// applied.
+    pub fn is_match(&self, root: &Path, path: &Path) -> bool {
+        // Patterns were checked when the filter was made.
+        let matcher = self.matcher(root).unwrap();
+        matcher.included(path)
+            && !matcher
+                .exclude
+                .matched_path_or_any_parents(path, false)
+                .is_ignore()
+    }
+
+    fn matcher(&self, root: &Path) -> Result<Matcher, ignore::Error> {
+        let build = |patterns: &[String]| {
+            let mut builder = GitignoreBuilder::new(root);
+            for pattern in patterns {
+                builder.add_line(None, pattern)?;
+            }
+            builder.build()
+        };
+
+        Ok(Matcher {
+            include: (!self.include.is_empty())
+                .then(|| build(&self.include))
+                .transpose()?,
+            exclude: build(&self.exclude)?,
+        })
+    }
+}
+
+struct Matcher {
+    include: Option<Gitignore>,
+    exclude: Gitignore,
+}
+
+impl Matcher {
+    fn included(&self, path: &Path) -> bool {
+        self.include
+            .as_ref()
+            .is_none_or(|include| include.matched_path_or_any_parents(path, false).is_ignore())
+    }
+}
+
+/// Every file under `dir` kept by `filter`, sorted so output is stable between
+/// runs, and the entries that could not be read.
+pub(crate) fn walk_files(dir: &Path, filter: &FileFilter) -> (Vec<PathBuf>, Vec<Error>) {
+    // Patterns were checked when the filter was made.
+    let matcher = filter.matcher(dir).unwrap();
+    match filter.mode {
+        WalkMode::All => walk_all(dir, matcher),
+        WalkMode::Unity => walk_unity(dir, matcher),
+    }
+}
+
+fn walk_all(dir: &Path, matcher: Matcher) -> (Vec<PathBuf>, Vec<Error>) {
+    let mut files = Vec::new();
+    let mut errors = Vec::new();
+
+    let entries = WalkDir::new(dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| {
+            entry.depth() == 0
+                || !matcher
+                    .exclude
+                    .matched(entry.path(), entry.file_type().is_dir())
+                    .is_ignore()
+        });
+
+    for entry in entries {
+        match entry {
+            Ok(entry) if entry.file_type().is_file() => {
+                if matcher.included(entry.path()) {
+                    files.push(entry.into_path());
+                }
+            }
+            Ok(_) => {}
+            Err(e) => errors.push(Error::Walk {
+                path: e.path().unwrap_or(dir).to_owned(),
+                source: e.into(),
+            }),
+        }
+    }
+
+    (files, errors)
+}
+
+fn walk_unity(dir: &Path, matcher: Matcher) -> (Vec<PathBuf>, Vec<Error>) {
+    let mut files = Vec::new();
+    let mut errors = Vec::new();
+
+    // The global gitignore is left out so every machine sees the same files.
+    let exclude = matcher.exclude.clone();
+    let entries = WalkBuilder::new(dir)
+        .standard_filters(false)
+        .git_ignore(true)
+        .git_exclude(true)
+        .ignore(true)
+        .parents(true)
+        .require_git(false)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .filter_entry(move |entry| {
+            let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+            entry.depth() == 0
+                || !(unity_hidden(entry.file_name())
+                    || exclude.matched(entry.path(), is_dir).is_ignore())
+        })
+        .build();
+
+    for entry in entries {
+        match entry {
+            Ok(entry) => {
+                if let Some(e) = entry.error() {
+                    log::warn!("{}", e);
+                }
+                let is_file = entry.file_type().is_some_and(|t| t.is_file());
+                if is_file && matcher.included(entry.path()) {
+                    files.push(entry.into_path());
+                }
+            }
+            Err(e) => {
+                let path = error_path(&e).unwrap_or(dir).to_owned();
+                let source = match e.io_error() {
+                    Some(_) => e.into_io_error().unwrap(),
+                    None => io::Error::other(e),
+                };
+                errors.push(Error::Walk { path, source });
+            }
+        }
+    }
+
+    (files, errors)
+}
+
+/// Whether Unity skips a file or folder when importing: hidden ones, backups
+/// ending in `~`, CVS metadata and temporary files.
+pub(crate) fn unity_hidden(name: &OsStr) -> bool {
+    let name = name.to_string_lossy();
+    name.starts_with('.')
+        || name.ends_with('~')
+        || name.eq_ignore_ascii_case("cvs")
+        || name.to_ascii_lowercase().ends_with(".tmp")
+}
+
+fn error_path(e: &ignore::Error) -> Option<&Path> {
+    match e {
+        ignore::Error::WithPath { path, .. } => Some(path),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+            error_path(err)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_match_applies_folder_patterns() {
+        let root = Path::new("/p");
+        let filter = FileFilter::with_default_exclude(vec![], vec![]).unwrap();
+        assert!(filter.is_match(root, Path::new("/p/Assets/a.mat.meta")));
+        assert!(filter.is_match(root, Path::new("/p/Assets/a.png.meta")));
+        assert!(!filter.is_match(
+            root,
+            Path::new("/p/Library/PackageCache/com.x/Runtime/s.shader.meta")
+        ));
+
+        let filter = FileFilter::new(vec!["Assets/".to_owned()], vec![]).unwrap();
+        assert!(filter.is_match(root, Path::new("/p/Assets/V/a.cs.meta")));
+        assert!(!filter.is_match(root, Path::new("/p/Packages/com.y/b.cs.meta")));
+    }
+}
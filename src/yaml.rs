@@ -0,0 +1,326 @@
+//! Finds the GUIDs in Unity's serialized YAML that are actually asset
+//! references, so everything else in a scene or prefab can be left alone.
+
+use yaml_rust::{
+    parser::{Event, MarkedEventReceiver, Parser},
+    scanner::Marker,
+};
+
+use crate::UUID_STR_LEN;
+
+/// Whether `contents` look like a Unity serialized YAML file.
+pub fn is_unity_yaml(contents: &[u8]) -> bool {
+    contents.starts_with(b"%YAML")
+}
+
+/// A `{fileID: ..., guid: ..., type: ...}` reference to another asset.
+pub struct Reference {
+    /// The line the GUID is on, starting at 1.
+    pub line: usize,
+    /// The fileID of the object holding the reference, from the header of
+    /// its document.
+    pub file_id: Option<String>,
+    pub guid: String,
+}
+
+/// Byte offsets of every GUID in `{fileID: ..., guid: ..., type: ...}`
+/// references, plus the top-level `guid:` key when `is_meta` is set.
+pub fn guid_offsets(contents: &str, is_meta: bool) -> Result<Vec<usize>, String> {
+    Ok(parse(contents, is_meta)?
+        .into_iter()
+        .map(|(n, _)| n)
+        .collect())
+}
+
+/// Every reference in `contents`, in file order.
+pub fn references(contents: &str) -> Result<Vec<Reference>, String> {
+    let line_starts = std::iter::once(0)
+        .chain(contents.match_indices('\n').map(|(n, _)| n + 1))
+        .collect::<Vec<_>>();
+
+    Ok(parse(contents, false)?
+        .into_iter()
+        .map(|(offset, file_id)| Reference {
+            line: line_starts.partition_point(|&start| start <= offset),
+            file_id: file_id.map(str::to_owned),
+            guid: contents[offset..offset + UUID_STR_LEN].to_owned(),
+        })
+        .collect())
+}
+
+/// `(offset, fileID of its document)` pairs for every GUID found.
+fn parse(contents: &str, is_meta: bool) -> Result<Vec<(usize, Option<&str>)>, String> {
+    let mut offsets = Vec::new();
+
+    for (start, body, file_id) in documents(contents) {
+        let mut receiver = Receiver {
+            body,
+            is_meta,
+            stack: Vec::new(),
+            offsets: Vec::new(),
+            char_offsets: (!body.is_ascii()).then(|| body.char_indices().map(|(n, _)| n).collect()),
+        };
+
+        Parser::new(body.chars())
+            .load(&mut receiver, false)
+            .map_err(|e| e.to_string())?;
+
+        for n in receiver.offsets {
+            offsets.push((start + n?, file_id));
+        }
+    }
+
+    Ok(offsets)
+}
+
+/// Splits a Unity YAML file into `(offset, body, fileID)` triples, one per
+/// `--- !u!<class> &<fileID>` document. The header lines are left out, since
+/// Unity's `stripped` suffix on them is not valid YAML.
+fn documents(contents: &str) -> Vec<(usize, &str, Option<&str>)> {
+    let mut documents = Vec::new();
+    let mut start = None;
+    let mut file_id = None;
+    let mut offset = 0;
+
+    for line in contents.split_inclusive('\n') {
+        if line.starts_with("---") {
+            if let Some(start) = start {
+                documents.push((start, &contents[start..offset], file_id));
+            }
+            start = Some(offset + line.len());
+            file_id = line
+                .split_whitespace()
+                .find_map(|word| word.strip_prefix('&'));
+        } else if start.is_none() && !line.starts_with('%') {
+            // A document without a header, as in .meta files.
+            start = Some(offset);
+        }
+        offset += line.len();
+    }
+
+    if let Some(start) = start {
+        documents.push((start, &contents[start..], file_id));
+    }
+
+    documents
+}
+
+enum Frame {
+    Mapping {
+        /// The key whose value comes next, or `None` if a key comes next.
+        key: Option<String>,
+        has_file_id: bool,
+        guid: Option<Result<usize, String>>,
+    },
+    Sequence,
+}
+
+struct Receiver<'a> {
+    body: &'a str,
+    is_meta: bool,
+    stack: Vec<Frame>,
+    offsets: Vec<Result<usize, String>>,
+    /// Byte offset of every char, only needed when they differ.
+    char_offsets: Option<Vec<usize>>,
+}
+
+impl Receiver<'_> {
+    /// Marks the value slot of the enclosing mapping, if any, as filled.
+    fn fill_value(&mut self) {
+        if let Some(Frame::Mapping { key, .. }) = self.stack.last_mut() {
+            *key = None;
+        }
+    }
+}
+
+/// Byte offset in `body` of the scalar `value` starting at `mark`.
+fn locate(
+    body: &str,
+    char_offsets: Option<&[usize]>,
+    value: &str,
+    mark: Marker,
+) -> Result<usize, String> {
+    let mut n = char_offsets.map_or(mark.index(), |offsets| offsets[mark.index()]);
+    if matches!(body.as_bytes().get(n), Some(b'"' | b'\'')) {
+        n += 1;
+    }
+
+    match body.get(n..n + UUID_STR_LEN) {
+        Some(guid) if guid == value => Ok(n),
+        _ => Err(format!(
+            "line {}: guid {:?} is not a plain 32 character GUID",
+            mark.line(),
+            value
+        )),
+    }
+}
+
+impl MarkedEventReceiver for Receiver<'_> {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        match ev {
+            Event::Scalar(value, ..) => match self.stack.last_mut() {
+                Some(Frame::Mapping {
+                    key: key @ None, ..
+                }) => *key = Some(value),
+                Some(Frame::Mapping {
+                    key: key @ Some(_),
+                    has_file_id,
+                    guid,
+                }) => match key.take().as_deref() {
+                    Some("fileID") => *has_file_id = true,
+                    Some("guid") => {
+                        let char_offsets = self.char_offsets.as_deref();
+                        *guid = Some(locate(self.body, char_offsets, &value, mark));
+                    }
+                    _ => {}
+                },
+                _ => {}
+            },
+            Event::MappingStart(..) => {
+                self.fill_value();
+                self.stack.push(Frame::Mapping {
+                    key: None,
+                    has_file_id: false,
+                    guid: None,
+                });
+            }
+            Event::SequenceStart(..) => {
+                self.fill_value();
+                self.stack.push(Frame::Sequence);
+            }
+            Event::MappingEnd => {
+                let Some(Frame::Mapping {
+                    has_file_id, guid, ..
+                }) = self.stack.pop()
+                else {
+                    return;
+                };
+
+                let is_meta_guid = self.is_meta && self.stack.is_empty();
+                if let (true, Some(guid)) = (has_file_id || is_meta_guid, guid) {
+                    self.offsets.push(guid);
+                }
+            }
+            Event::SequenceEnd => {
+                self.stack.pop();
+            }
+            Event::Alias(..) => {
+                self.fill_value();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A: &str = "0123456789abcdef0123456789abcdef";
+    const B: &str = "fedcba9876543210fedcba9876543210";
+
+    fn unity(body: &str) -> String {
+        format!("%YAML 1.1\n%TAG !u! tag:unity3d.com,2011:\n{}", body)
+    }
+
+    #[test]
+    fn stripped_documents() {
+        let contents = unity(&format!(
+            "--- !u!1001 &100100 stripped\n\
+             PrefabInstance:\n  \
+             m_SourcePrefab: {{fileID: 100100000, guid: {}, type: 3}}\n\
+             --- !u!4 &-200\n\
+             Transform:\n  \
+             m_Father: {{fileID: 400000, guid: {}, type: 3}}\n",
+            A, B
+        ));
+
+        let offsets = guid_offsets(&contents, false).unwrap();
+        assert_eq!(
+            offsets,
+            [contents.find(A).unwrap(), contents.find(B).unwrap()]
+        );
+
+        let references = references(&contents).unwrap();
+        assert_eq!(references[0].line, 5);
+        assert_eq!(references[0].file_id.as_deref(), Some("100100"));
+        assert_eq!(references[1].line, 8);
+        assert_eq!(references[1].file_id.as_deref(), Some("-200"));
+        assert_eq!(references[1].guid, B);
+    }
+
+    #[test]
+    fn non_ascii_text_before_a_reference() {
+        let contents = unity(&format!(
+            "--- !u!21 &2100000\n\
+             Material:\n  \
+             m_Name: Größe 日本語\n  \
+             m_Tex: {{fileID: 2800000, guid: {}, type: 3}}\n",
+            A
+        ));
+
+        assert_eq!(
+            guid_offsets(&contents, false).unwrap(),
+            [contents.find(A).unwrap()]
+        );
+    }
+
+    #[test]
+    fn wrapped_flow_mapping() {
+        let contents = unity(&format!(
+            "--- !u!114 &1\n\
+             MonoBehaviour:\n  \
+             m_Script: {{fileID: 11500000, guid: {},\n    type: 3}}\n",
+            A
+        ));
+
+        assert_eq!(
+            guid_offsets(&contents, false).unwrap(),
+            [contents.find(A).unwrap()]
+        );
+    }
+
+    #[test]
+    fn quoted_guids() {
+        let contents = unity(&format!(
+            "--- !u!114 &1\n\
+             MonoBehaviour:\n  \
+             a: {{fileID: 1, guid: \"{}\", type: 3}}\n  \
+             b: {{fileID: 1, guid: '{}', type: 3}}\n",
+            A, B
+        ));
+
+        assert_eq!(
+            guid_offsets(&contents, false).unwrap(),
+            [contents.find(A).unwrap(), contents.find(B).unwrap()]
+        );
+    }
+
+    #[test]
+    fn meta_guid() {
+        let contents = format!(
+            "fileFormatVersion: 2\nguid: {}\nTextureImporter:\n  settings:\n    guid: {}\n",
+            A, B
+        );
+
+        assert_eq!(
+            guid_offsets(&contents, true).unwrap(),
+            [contents.find(A).unwrap()]
+        );
+        assert!(guid_offsets(&contents, false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn non_references_are_skipped() {
+        let contents = unity(&format!(
+            "--- !u!114 &1\n\
+             MonoBehaviour:\n  \
+             m_AssetGUID: {}\n  \
+             m_Entry: {{guid: {}, type: 3}}\n  \
+             guid: {}\n",
+            A, B, A
+        ));
+
+        assert!(guid_offsets(&contents, false).unwrap().is_empty());
+    }
+}